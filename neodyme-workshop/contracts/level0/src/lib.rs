@@ -1,6 +1,5 @@
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
-    entrypoint,
     instruction::{AccountMeta, Instruction},
     pubkey::Pubkey,
     system_program, sysvar,
@@ -48,8 +47,10 @@ pub struct Wallet {
 pub const WALLET_LEN: u64 = 32 + 32;
 
 pub mod processor;
+#[cfg(not(feature = "no-entrypoint"))]
 use processor::process_instruction;
-entrypoint!(process_instruction);
+#[cfg(not(feature = "no-entrypoint"))]
+solana_program::entrypoint!(process_instruction);
 
 pub fn get_wallet_address(authority: Pubkey, wallet_program: Pubkey) -> Pubkey {
     let (wallet_address, _) =
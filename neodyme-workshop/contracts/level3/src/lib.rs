@@ -1,6 +1,5 @@
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
-    entrypoint,
     instruction::{AccountMeta, Instruction},
     pubkey::Pubkey,
     system_program, sysvar,
@@ -69,8 +68,10 @@ pub struct Vault {
 pub const VAULT_LEN: u64 = 32 + 8 + 32 + 1;
 
 pub mod processor;
+#[cfg(not(feature = "no-entrypoint"))]
 use processor::process_instruction;
-entrypoint!(process_instruction);
+#[cfg(not(feature = "no-entrypoint"))]
+solana_program::entrypoint!(process_instruction);
 
 pub fn initialize(
     tip_program: Pubkey,
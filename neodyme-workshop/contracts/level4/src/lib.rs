@@ -1,6 +1,5 @@
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
-    entrypoint,
     instruction::{AccountMeta, Instruction},
     pubkey::Pubkey,
     system_program, sysvar,
@@ -49,8 +48,10 @@ pub enum WalletInstruction {
 }
 
 pub mod processor;
+#[cfg(not(feature = "no-entrypoint"))]
 use processor::process_instruction;
-entrypoint!(process_instruction);
+#[cfg(not(feature = "no-entrypoint"))]
+solana_program::entrypoint!(process_instruction);
 
 pub fn get_wallet_address(owner: &Pubkey, wallet_program: &Pubkey) -> (Pubkey, u8) {
     Pubkey::find_program_address(&[&owner.to_bytes()], wallet_program)
@@ -0,0 +1,285 @@
+use std::collections::{BTreeMap, HashSet};
+
+use solana_program::{instruction::Instruction, program_pack::Pack, pubkey::Pubkey};
+use solana_runtime::accounts_index::ScanConfig;
+use solana_sdk::{
+    account::ReadableAccount,
+    signature::{Keypair, Signature},
+};
+
+use crate::{clone_keypair, Environment, ExecutionOutcome, LocalEnvironment};
+
+/// The SOL and SPL-token holdings of an account, as recorded by a [BalanceTracker].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Holdings {
+    pub lamports: u64,
+    /// The sum of all token accounts owned by the account, by mint. Summed in u128, since the balances of several
+    /// accounts of one mint can exceed u64.
+    pub tokens: BTreeMap<Pubkey, u128>,
+}
+
+/// The change of the holdings of an attacker since the [BalanceTracker] was created.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HoldingsChange {
+    pub owner: Pubkey,
+    pub lamports: i128,
+    /// The change of the token balance by mint. Mints with an unchanged balance are left out.
+    pub tokens: BTreeMap<Pubkey, i128>,
+}
+
+impl HoldingsChange {
+    /// Returns whether the attacker gained anything, no matter what was lost for it.
+    pub fn is_profit(&self) -> bool {
+        self.lamports > 0 || self.tokens.values().any(|&delta| delta > 0)
+    }
+}
+
+/// Records the holdings of a set of attacker keys, to measure the profit of an exploit.
+pub struct BalanceTracker {
+    initial: BTreeMap<Pubkey, Holdings>,
+}
+
+impl BalanceTracker {
+    /// Records the current holdings of the attackers.
+    pub fn new(env: &LocalEnvironment, attackers: &[Pubkey]) -> Self {
+        BalanceTracker {
+            initial: attackers
+                .iter()
+                .map(|attacker| (*attacker, env.holdings(*attacker)))
+                .collect(),
+        }
+    }
+
+    /// The changes of the holdings of all attackers since the tracker was created.
+    pub fn changes(&self, env: &LocalEnvironment) -> Vec<HoldingsChange> {
+        self.initial
+            .iter()
+            .map(|(owner, initial)| {
+                let current = env.holdings(*owner);
+                let mut tokens = BTreeMap::new();
+                for mint in initial.tokens.keys().chain(current.tokens.keys()) {
+                    let delta = *current.tokens.get(mint).unwrap_or(&0) as i128
+                        - *initial.tokens.get(mint).unwrap_or(&0) as i128;
+                    if delta != 0 {
+                        tokens.insert(*mint, delta);
+                    }
+                }
+                HoldingsChange {
+                    owner: *owner,
+                    lamports: current.lamports as i128 - initial.lamports as i128,
+                    tokens,
+                }
+            })
+            .collect()
+    }
+}
+
+/// A transaction that broke an [Invariant].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Violation {
+    pub signature: Signature,
+    pub slot: u64,
+    pub message: String,
+}
+
+type Check = Box<dyn FnMut(&LocalEnvironment, &ExecutionOutcome) -> Result<(), String>>;
+
+/// A property of the environment that is checked after every transaction, once added with [LocalEnvironment::add_invariant].
+pub struct Invariant {
+    name: String,
+    check: Check,
+    violations: Vec<Violation>,
+}
+
+impl Invariant {
+    /// An invariant with an arbitrary check. The check returns an error message if the invariant does not hold after the transaction.
+    pub fn new<F>(name: &str, check: F) -> Self
+    where
+        F: FnMut(&LocalEnvironment, &ExecutionOutcome) -> Result<(), String> + 'static,
+    {
+        Invariant {
+            name: name.to_string(),
+            check: Box::new(check),
+            violations: vec![],
+        }
+    }
+
+    /// The total lamports of all accounts owned by the program never decrease, except by transactions signed by one of the authorities.
+    /// The change is computed from the pre and post balances of the transaction, so the invariant stays correct after [LocalEnvironment::restore].
+    /// Accounts that a transaction closes or reassigns still count if the program owned them when the invariant last looked.
+    pub fn program_lamports_never_decrease(
+        env: &LocalEnvironment,
+        program: Pubkey,
+        authorities: &[Pubkey],
+    ) -> Self {
+        let authorities = authorities.to_vec();
+        let mut known: HashSet<Pubkey> = env.program_account_keys(program).collect();
+        Self::new(
+            &format!("lamports of program {} never decrease", program),
+            move |env, outcome| {
+                let (mut previous, mut total) = (0u128, 0u128);
+                for change in &outcome.lamport_changes {
+                    let owned = known.contains(&change.pubkey)
+                        || env
+                            .get_account(change.pubkey)
+                            .is_some_and(|account| account.owner == program);
+                    if owned {
+                        previous += change.pre as u128;
+                        total += change.post as u128;
+                    }
+                }
+                known.extend(env.program_account_keys(program));
+                let message = &outcome.transaction.message;
+                let signers = &message.static_account_keys()
+                    [..message.header().num_required_signatures as usize];
+                if total < previous && !signers.iter().any(|signer| authorities.contains(signer)) {
+                    return Err(format!(
+                        "decreased by {} lamports without a signature of an authority",
+                        previous - total
+                    ));
+                }
+                Ok(())
+            },
+        )
+    }
+
+    /// The instructions can still be executed successfully by the signers, like an authority withdrawing its funds.
+    /// This is checked by simulation, so the environment is not changed.
+    pub fn can_execute(name: &str, instructions: &[Instruction], signers: &[&Keypair]) -> Self {
+        let instructions = instructions.to_vec();
+        let signers: Vec<Keypair> = signers.iter().map(|signer| clone_keypair(signer)).collect();
+        Self::new(name, move |env, _| {
+            let signers = signers.iter().collect::<Vec<_>>();
+            let simulation = env.simulate_as_transaction(&instructions, &signers);
+            match simulation.status {
+                Ok(()) => Ok(()),
+                Err(err) => Err(format!("failed with {}", err)),
+            }
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The transactions that broke the invariant so far.
+    pub fn violations(&self) -> &[Violation] {
+        &self.violations
+    }
+}
+
+/// The result of an invariant, as shown in a [Report].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvariantResult {
+    pub name: String,
+    pub violations: Vec<Violation>,
+}
+
+/// The outcome of a PoC: the changes of the attacker holdings and the results of all invariants.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Report {
+    pub changes: Vec<HoldingsChange>,
+    pub invariants: Vec<InvariantResult>,
+}
+
+impl Report {
+    /// Returns whether any attacker gained SOL or tokens.
+    pub fn attacker_profited(&self) -> bool {
+        self.changes.iter().any(HoldingsChange::is_profit)
+    }
+
+    /// Returns whether no invariant was broken.
+    pub fn invariants_held(&self) -> bool {
+        self.invariants
+            .iter()
+            .all(|invariant| invariant.violations.is_empty())
+    }
+
+    /// Returns whether the exploit succeeded, i.e. an attacker profited or an invariant was broken.
+    pub fn exploit_succeeded(&self) -> bool {
+        self.attacker_profited() || !self.invariants_held()
+    }
+
+    /// Prints the report. Every check is a property of the attacked program, so FAIL means the exploit broke it.
+    pub fn print(&self) {
+        println!("REPORT");
+        for change in &self.changes {
+            let status = if change.is_profit() { "FAIL" } else { "PASS" };
+            println!("  [{}] attacker {} does not profit", status, change.owner);
+            println!("         lamports: {:+}", change.lamports);
+            for (mint, delta) in &change.tokens {
+                println!("         tokens of mint {}: {:+}", mint, delta);
+            }
+        }
+        for invariant in &self.invariants {
+            let status = if invariant.violations.is_empty() {
+                "PASS"
+            } else {
+                "FAIL"
+            };
+            println!("  [{}] {}", status, invariant.name);
+            for violation in &invariant.violations {
+                println!(
+                    "         broken by {} in slot {}: {}",
+                    violation.signature, violation.slot, violation.message
+                );
+            }
+        }
+        if self.exploit_succeeded() {
+            println!("RESULT: FAIL, the exploit succeeded");
+        } else {
+            println!("RESULT: PASS, the exploit did not succeed");
+        }
+    }
+}
+
+impl LocalEnvironment {
+    /// Checks all invariants against the state after the transaction.
+    pub(crate) fn check_invariants(&mut self, outcome: &ExecutionOutcome) {
+        let mut invariants = std::mem::take(&mut self.invariants);
+        for invariant in &mut invariants {
+            if let Err(message) = (invariant.check)(self, outcome) {
+                invariant.violations.push(Violation {
+                    signature: outcome.transaction.signatures[0],
+                    slot: outcome.slot,
+                    message,
+                });
+            }
+        }
+        self.invariants = invariants;
+    }
+
+    fn holdings(&self, owner: Pubkey) -> Holdings {
+        let mut tokens = BTreeMap::new();
+        let token_accounts = self
+            .bank
+            .get_filtered_program_accounts(
+                &spl_token::ID,
+                |account| {
+                    account.data().len() == spl_token::state::Account::LEN
+                        && account.data()[32..64] == owner.to_bytes()
+                },
+                &ScanConfig::default(),
+            )
+            .expect("couldn't scan token accounts");
+        for (_, account) in token_accounts {
+            if let Ok(token_account) = spl_token::state::Account::unpack(account.data()) {
+                *tokens.entry(token_account.mint).or_insert(0) += token_account.amount as u128;
+            }
+        }
+        Holdings {
+            lamports: self
+                .get_account(owner)
+                .map_or(0, |account| account.lamports),
+            tokens,
+        }
+    }
+
+    fn program_account_keys(&self, program: Pubkey) -> impl Iterator<Item = Pubkey> {
+        self.bank
+            .get_program_accounts(&program, &ScanConfig::default())
+            .expect("couldn't scan program accounts")
+            .into_iter()
+            .map(|(pubkey, _)| pubkey)
+    }
+}
@@ -0,0 +1,38 @@
+use std::{env, process};
+
+use poc_framework::{PrintableTransaction, TransactionCapture};
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: replay <capture file>");
+            process::exit(2);
+        }
+    };
+
+    let capture = TransactionCapture::load(&path);
+    if !capture.builtin_programs.is_empty() {
+        eprintln!(
+            "the capture uses native programs, which the replay binary does not support: {:?}",
+            capture.builtin_programs
+        );
+        eprintln!("replay it with TransactionCapture::replay_with and a builder that adds them with add_builtin_program");
+        process::exit(2);
+    }
+    let replay = capture.replay();
+    replay.outcome.print_named(&path);
+
+    if !replay.status_matches {
+        println!("status differs: recorded {:?}", capture.status);
+    }
+    for mismatch in &replay.mismatches {
+        println!("account {} differs", mismatch.pubkey);
+        println!("  recorded: {:?}", mismatch.recorded);
+        println!("  replayed: {:?}", mismatch.replayed);
+    }
+    if !replay.is_faithful() {
+        process::exit(1);
+    }
+    println!("replay matches the recorded execution");
+}
@@ -0,0 +1,437 @@
+//! Runs the native `process_instruction` of a program as a builtin, instead of its compiled BPF code.
+//! Adapted from `solana-program-test`, with support for accounts that are resized during a CPI.
+//! The runtime already logs the invocations of builtins, so unlike there nothing is logged here.
+//! A panic of a native program fails the instruction, like an abort of a BPF program, and is logged instead of printed.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    convert::TryFrom,
+    panic::{self, AssertUnwindSafe},
+    rc::Rc,
+    slice,
+    sync::{Arc, Once},
+};
+
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::{ProcessInstruction, ProgramResult, MAX_PERMITTED_DATA_INCREASE, SUCCESS},
+    instruction::{Instruction, InstructionError},
+    program_error::{ProgramError, ACCOUNT_BORROW_FAILED, UNSUPPORTED_SYSVAR},
+    program_stubs,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+use solana_program_runtime::{ic_msg, invoke_context::InvokeContext, timings::ExecuteTimings};
+use solana_sdk::account::ReadableAccount;
+
+thread_local! {
+    static INVOKE_CONTEXT: RefCell<Option<usize>> = const { RefCell::new(None) };
+    /// The capacity of the account data buffers handed to the native programs, by address.
+    static DATA_CAPACITY: RefCell<HashMap<usize, usize>> = RefCell::new(HashMap::new());
+    /// The number of native programs currently executing, including the ones waiting for a CPI to return.
+    static EXECUTING: Cell<usize> = const { Cell::new(0) };
+    /// The message of the panic of a native program that is unwinding and not caught yet.
+    static UNWINDING: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+fn set_invoke_context(new: &mut InvokeContext) {
+    INVOKE_CONTEXT
+        .with(|invoke_context| invoke_context.replace(Some(new as *mut InvokeContext as usize)));
+}
+
+fn get_invoke_context<'a, 'b>() -> &'a mut InvokeContext<'b> {
+    let ptr = INVOKE_CONTEXT.with(|invoke_context| match *invoke_context.borrow() {
+        Some(val) => val,
+        None => panic!("Invoke context not set!"),
+    });
+    unsafe { &mut *(ptr as *mut InvokeContext) }
+}
+
+/// Installs the syscall stubs, so the native programs can log, invoke other programs and read sysvars,
+/// and the panic hook that logs the panics of the native programs.
+pub(crate) fn install_syscall_stubs() {
+    static ONCE: Once = Once::new();
+    ONCE.call_once(|| {
+        program_stubs::set_syscall_stubs(Box::new(SyscallStubs {}));
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if EXECUTING.with(Cell::get) == 0 {
+                default_hook(info);
+                return;
+            }
+            match UNWINDING.with(|unwinding| unwinding.borrow_mut().take()) {
+                // A panic while unwinding aborts the process before the transaction log is printed
+                Some(first) => {
+                    eprintln!("native program {}", first);
+                    default_hook(info);
+                }
+                None => {
+                    let invoke_context = get_invoke_context();
+                    ic_msg!(invoke_context, "Program log: {}", info);
+                    UNWINDING.with(|unwinding| unwinding.replace(Some(info.to_string())));
+                }
+            }
+        }));
+    });
+}
+
+/// Calls a native program with the accounts of the current instruction and commits the changes it made.
+/// Used by the [processor] macro.
+pub fn builtin_process_instruction(
+    process_instruction: ProcessInstruction,
+    _first_instruction_account: usize,
+    invoke_context: &mut InvokeContext,
+) -> Result<(), InstructionError> {
+    set_invoke_context(invoke_context);
+
+    let transaction_context = &invoke_context.transaction_context;
+    let instruction_context = transaction_context.get_current_instruction_context()?;
+    let instruction_data = instruction_context.get_instruction_data();
+    let instruction_account_indices = 0..instruction_context.get_number_of_instruction_accounts();
+
+    let program_id = *instruction_context.get_last_program_key(transaction_context)?;
+
+    // Accounts passed more than once share one copy, otherwise the changes of one would overwrite the other
+    let mut deduplicated_indices = vec![];
+    for index in instruction_account_indices.clone() {
+        if instruction_context
+            .is_instruction_account_duplicate(index)?
+            .is_none()
+        {
+            deduplicated_indices.push(index);
+        }
+    }
+
+    // Create copies of the accounts. The data gets the same room to grow as in the BPF serialization.
+    let mut account_copies = deduplicated_indices
+        .iter()
+        .map(|instruction_account_index| {
+            let borrowed_account = instruction_context
+                .try_borrow_instruction_account(transaction_context, *instruction_account_index)?;
+            let data_len = borrowed_account.get_data().len();
+            let mut data = vec![0; data_len + MAX_PERMITTED_DATA_INCREASE];
+            data[..data_len].copy_from_slice(borrowed_account.get_data());
+            Ok((
+                *borrowed_account.get_key(),
+                *borrowed_account.get_owner(),
+                borrowed_account.get_lamports(),
+                data,
+                data_len,
+            ))
+        })
+        .collect::<Result<Vec<_>, InstructionError>>()?;
+
+    // Create shared references to account_copies
+    let account_refs: Vec<_> = account_copies
+        .iter_mut()
+        .map(|(key, owner, lamports, data, data_len)| {
+            DATA_CAPACITY.with(|capacity| {
+                capacity
+                    .borrow_mut()
+                    .insert(data.as_ptr() as usize, data.len())
+            });
+            (
+                &*key,
+                &*owner,
+                Rc::new(RefCell::new(lamports)),
+                Rc::new(RefCell::new(&mut data[..*data_len])),
+            )
+        })
+        .collect();
+
+    // Create AccountInfos
+    let account_infos = instruction_account_indices
+        .map(|instruction_account_index| {
+            let first_index = instruction_context
+                .is_instruction_account_duplicate(instruction_account_index)?
+                .unwrap_or(instruction_account_index);
+            let account_copy_index = deduplicated_indices
+                .iter()
+                .position(|index| *index == first_index)
+                .unwrap();
+            let (key, owner, lamports, data) = &account_refs[account_copy_index];
+            let borrowed_account = instruction_context
+                .try_borrow_instruction_account(transaction_context, instruction_account_index)?;
+            Ok(AccountInfo {
+                key,
+                is_signer: borrowed_account.is_signer(),
+                is_writable: borrowed_account.is_writable(),
+                lamports: lamports.clone(),
+                data: data.clone(),
+                owner,
+                executable: borrowed_account.is_executable(),
+                rent_epoch: borrowed_account.get_rent_epoch(),
+            })
+        })
+        .collect::<Result<Vec<AccountInfo>, InstructionError>>()?;
+
+    // Execute the program. The runtime logs its invocation and result.
+    EXECUTING.with(|executing| executing.set(executing.get() + 1));
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        process_instruction(&program_id, &account_infos, instruction_data)
+    }));
+    EXECUTING.with(|executing| executing.set(executing.get() - 1));
+    UNWINDING.with(|unwinding| unwinding.take());
+
+    let changes = account_refs
+        .iter()
+        .map(|(_, owner, lamports, data)| (**owner, **lamports.borrow(), data.borrow().to_vec()))
+        .collect::<Vec<_>>();
+    drop(account_infos);
+    drop(account_refs);
+    for (_, _, _, data, _) in &account_copies {
+        DATA_CAPACITY.with(|capacity| capacity.borrow_mut().remove(&(data.as_ptr() as usize)));
+    }
+
+    result
+        .map_err(|_| InstructionError::ProgramFailedToComplete)?
+        .map_err(|err| InstructionError::from(u64::from(err)))?;
+
+    // Commit AccountInfo changes back into the transaction context.
+    // A CPI of the program may have moved the instruction context, so it is looked up again.
+    let transaction_context = &invoke_context.transaction_context;
+    let instruction_context = transaction_context.get_current_instruction_context()?;
+    for (instruction_account_index, (owner, lamports, data)) in
+        deduplicated_indices.into_iter().zip(changes)
+    {
+        let mut borrowed_account = instruction_context
+            .try_borrow_instruction_account(transaction_context, instruction_account_index)?;
+        if borrowed_account.get_lamports() != lamports {
+            borrowed_account.set_lamports(lamports)?;
+        }
+        if borrowed_account.get_data() != data {
+            borrowed_account.set_data(&data)?;
+        }
+        if borrowed_account.get_owner() != &owner {
+            borrowed_account.set_owner(owner.as_ref())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Converts the `process_instruction` of a `solana-program` style program into a builtin,
+/// for use with [LocalEnvironmentBuilder::add_builtin_program](crate::LocalEnvironmentBuilder::add_builtin_program).
+#[macro_export]
+macro_rules! processor {
+    ($process_instruction:expr) => {
+        |first_instruction_account: usize,
+         invoke_context: &mut $crate::solana_program_runtime::invoke_context::InvokeContext| {
+            $crate::builtin_process_instruction(
+                $process_instruction,
+                first_instruction_account,
+                invoke_context,
+            )
+        }
+    };
+}
+
+fn get_sysvar<T: Default + Sysvar + Sized + serde::de::DeserializeOwned + Clone>(
+    sysvar: Result<Arc<T>, InstructionError>,
+    var_addr: *mut u8,
+) -> u64 {
+    let invoke_context = get_invoke_context();
+    if invoke_context
+        .get_compute_meter()
+        .try_borrow_mut()
+        .map_err(|_| ACCOUNT_BORROW_FAILED)
+        .unwrap()
+        .consume(invoke_context.get_compute_budget().sysvar_base_cost + T::size_of() as u64)
+        .is_err()
+    {
+        panic!("Exceeded compute budget");
+    }
+
+    match sysvar {
+        Ok(sysvar_data) => unsafe {
+            *(var_addr as *mut _ as *mut T) = T::clone(&sysvar_data);
+            SUCCESS
+        },
+        Err(_) => UNSUPPORTED_SYSVAR,
+    }
+}
+
+/// Points the data of the account info to the new length, within the buffer created by [builtin_process_instruction].
+fn resize_account_info_data(account_info: &AccountInfo, new_len: usize) {
+    let mut data = account_info.data.borrow_mut();
+    let capacity = DATA_CAPACITY.with(|capacity| {
+        capacity
+            .borrow()
+            .get(&(data.as_ptr() as usize))
+            .copied()
+            .unwrap_or(data.len())
+    });
+    assert!(
+        new_len <= capacity,
+        "account {} can't be resized from {} to {} bytes in a CPI",
+        account_info.key,
+        data.len(),
+        new_len
+    );
+    unsafe {
+        *data = slice::from_raw_parts_mut(data.as_mut_ptr(), new_len);
+    }
+}
+
+struct SyscallStubs {}
+impl program_stubs::SyscallStubs for SyscallStubs {
+    fn sol_log(&self, message: &str) {
+        let invoke_context = get_invoke_context();
+        ic_msg!(invoke_context, "Program log: {}", message);
+    }
+
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let invoke_context = get_invoke_context();
+        let transaction_context = &invoke_context.transaction_context;
+        let instruction_context = transaction_context
+            .get_current_instruction_context()
+            .unwrap();
+        let caller = instruction_context
+            .get_last_program_key(transaction_context)
+            .unwrap();
+
+        let signers = signers_seeds
+            .iter()
+            .map(|seeds| Pubkey::create_program_address(seeds, caller))
+            .collect::<Result<Vec<_>, _>>()?;
+        // A missing signature or a writable account that isn't writable for the caller fails the CPI, like on chain
+        let (instruction_accounts, program_indices) = invoke_context
+            .prepare_instruction(instruction, &signers)
+            .map_err(|err| ProgramError::try_from(err).unwrap_or_else(|err| panic!("{}", err)))?;
+
+        // Copy caller's account_info modifications into invoke_context accounts
+        let transaction_context = &invoke_context.transaction_context;
+        let instruction_context = transaction_context
+            .get_current_instruction_context()
+            .unwrap();
+        let mut account_indices = Vec::with_capacity(instruction_accounts.len());
+        for instruction_account in instruction_accounts.iter() {
+            let account_key = transaction_context
+                .get_key_of_account_at_index(instruction_account.index_in_transaction)
+                .unwrap();
+            let account_info_index = account_infos
+                .iter()
+                .position(|account_info| account_info.unsigned_key() == account_key)
+                .ok_or(ProgramError::NotEnoughAccountKeys)?;
+            let account_info = &account_infos[account_info_index];
+            let mut borrowed_account = instruction_context
+                .try_borrow_instruction_account(
+                    transaction_context,
+                    instruction_account.index_in_caller,
+                )
+                .unwrap();
+            if borrowed_account.get_lamports() != account_info.lamports() {
+                borrowed_account
+                    .set_lamports(account_info.lamports())
+                    .unwrap();
+            }
+            let account_info_data = account_info.try_borrow_data().unwrap();
+            if borrowed_account.get_data() != *account_info_data {
+                borrowed_account.set_data(&account_info_data).unwrap();
+            }
+            if borrowed_account.is_executable() != account_info.executable {
+                borrowed_account
+                    .set_executable(account_info.executable)
+                    .unwrap();
+            }
+            if borrowed_account.get_owner() != account_info.owner {
+                borrowed_account
+                    .set_owner(account_info.owner.as_ref())
+                    .unwrap();
+            }
+            drop(borrowed_account);
+            let account = transaction_context
+                .get_account_at_index(instruction_account.index_in_transaction)
+                .unwrap()
+                .borrow();
+            assert_eq!(account.rent_epoch(), account_info.rent_epoch);
+            if instruction_account.is_writable {
+                account_indices.push((instruction_account.index_in_caller, account_info_index));
+            }
+        }
+
+        let mut compute_units_consumed = 0;
+        invoke_context
+            .process_instruction(
+                &instruction.data,
+                &instruction_accounts,
+                &program_indices,
+                &mut compute_units_consumed,
+                &mut ExecuteTimings::default(),
+            )
+            .map_err(|err| ProgramError::try_from(err).unwrap_or_else(|err| panic!("{}", err)))?;
+
+        // Copy invoke_context accounts modifications into caller's account_info
+        let transaction_context = &invoke_context.transaction_context;
+        let instruction_context = transaction_context
+            .get_current_instruction_context()
+            .unwrap();
+        for (index_in_caller, account_info_index) in account_indices.into_iter() {
+            let borrowed_account = instruction_context
+                .try_borrow_instruction_account(transaction_context, index_in_caller)
+                .unwrap();
+            let account_info = &account_infos[account_info_index];
+            **account_info.try_borrow_mut_lamports().unwrap() = borrowed_account.get_lamports();
+            if account_info.owner != borrowed_account.get_owner() {
+                account_info.assign(borrowed_account.get_owner());
+            }
+            let new_data = borrowed_account.get_data();
+            if account_info.data_len() != new_data.len() {
+                resize_account_info_data(account_info, new_data.len());
+            }
+            account_info
+                .try_borrow_mut_data()?
+                .copy_from_slice(new_data);
+        }
+
+        Ok(())
+    }
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        get_sysvar(
+            get_invoke_context().get_sysvar_cache().get_clock(),
+            var_addr,
+        )
+    }
+
+    fn sol_get_epoch_schedule_sysvar(&self, var_addr: *mut u8) -> u64 {
+        get_sysvar(
+            get_invoke_context().get_sysvar_cache().get_epoch_schedule(),
+            var_addr,
+        )
+    }
+
+    #[allow(deprecated)]
+    fn sol_get_fees_sysvar(&self, var_addr: *mut u8) -> u64 {
+        get_sysvar(get_invoke_context().get_sysvar_cache().get_fees(), var_addr)
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        get_sysvar(get_invoke_context().get_sysvar_cache().get_rent(), var_addr)
+    }
+
+    fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        let (program_id, data) = get_invoke_context().transaction_context.get_return_data();
+        Some((*program_id, data.to_vec()))
+    }
+
+    fn sol_set_return_data(&self, data: &[u8]) {
+        let invoke_context = get_invoke_context();
+        let transaction_context = &mut invoke_context.transaction_context;
+        let instruction_context = transaction_context
+            .get_current_instruction_context()
+            .unwrap();
+        let caller = *instruction_context
+            .get_last_program_key(transaction_context)
+            .unwrap();
+        transaction_context
+            .set_return_data(caller, data.to_vec())
+            .unwrap();
+    }
+}
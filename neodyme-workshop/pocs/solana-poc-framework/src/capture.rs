@@ -0,0 +1,280 @@
+use std::{fs::File, path::Path};
+
+use serde::{Deserialize, Serialize};
+use solana_program::{
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+    clock::{Clock, Slot},
+    epoch_schedule::EpochSchedule,
+    fee_calculator::FeeRateGovernor,
+    pubkey::Pubkey,
+    rent::Rent,
+    slot_hashes::SlotHashes,
+    stake_history::StakeHistory,
+    sysvar::{Sysvar, SysvarId},
+};
+use solana_sdk::{
+    account::{from_account, Account},
+    feature_set::{self, FeatureSet},
+    transaction::{MessageHash, SanitizedTransaction, TransactionError, VersionedTransaction},
+};
+
+use crate::{Environment, ExecutionOutcome, LocalEnvironment, LocalEnvironmentBuilder};
+
+/// The version of the capture file format. Captures of other versions can not be loaded.
+pub const CAPTURE_VERSION: u32 = 1;
+
+/// The sysvars a transaction was executed with.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CapturedSysvars {
+    pub clock: Clock,
+    pub rent: Rent,
+    pub epoch_schedule: EpochSchedule,
+    pub slot_hashes: SlotHashes,
+    pub stake_history: StakeHistory,
+}
+
+/// A transaction executed by a [LocalEnvironment], together with everything required to execute it again.
+/// Recorded by [LocalEnvironment::capture_transactions].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransactionCapture {
+    /// Always [CAPTURE_VERSION] for captures created by this version of the framework. Must be the first field.
+    pub version: u32,
+    pub slot: Slot,
+    pub transaction: VersionedTransaction,
+    /// The fee rate of the recent blockhash of the transaction.
+    pub lamports_per_signature: u64,
+    pub sysvars: CapturedSysvars,
+    pub active_features: Vec<Pubkey>,
+    /// The compute unit limit of the environment. None if the limit was requested by the transaction.
+    pub compute_max_units: Option<u64>,
+    /// The native programs of the transaction that were added with [crate::LocalEnvironmentBuilder::add_builtin_program].
+    /// Their code is not part of the capture, so they have to be added again to replay it, see [Self::replay_with].
+    pub builtin_programs: Vec<Pubkey>,
+    /// The state of all accounts the transaction could access before it was executed. None if the account did not exist.
+    pub pre_accounts: Vec<(Pubkey, Option<Account>)>,
+    /// The state of the same accounts after the transaction was executed.
+    pub post_accounts: Vec<(Pubkey, Option<Account>)>,
+    pub status: Result<(), TransactionError>,
+    pub logs: Vec<String>,
+}
+
+/// An account whose state after a replay differs from the recorded one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccountMismatch {
+    pub pubkey: Pubkey,
+    pub recorded: Option<Account>,
+    pub replayed: Option<Account>,
+}
+
+/// The result of replaying a [TransactionCapture].
+#[derive(Clone, Debug)]
+pub struct Replay {
+    pub outcome: ExecutionOutcome,
+    /// Whether the replay failed with the same error as the recorded execution, or succeeded like it.
+    pub status_matches: bool,
+    pub mismatches: Vec<AccountMismatch>,
+}
+
+impl Replay {
+    /// Returns whether the replay ended exactly like the recorded execution.
+    pub fn is_faithful(&self) -> bool {
+        self.status_matches && self.mismatches.is_empty()
+    }
+}
+
+impl TransactionCapture {
+    /// Loads a capture from a file. Panics if the file was written by an incompatible version of the framework.
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let data = std::fs::read(path).expect("couldn't read capture");
+        let version: u32 = bincode::deserialize(&data).expect("couldn't read capture version");
+        if version != CAPTURE_VERSION {
+            panic!(
+                "capture has version {}, but only version {} is supported",
+                version, CAPTURE_VERSION
+            );
+        }
+        bincode::deserialize(&data).expect("couldn't deserialize capture")
+    }
+
+    /// Writes the capture to a file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) {
+        let file = File::create(path).expect("couldn't create capture");
+        bincode::serialize_into(file, self).expect("couldn't write capture");
+    }
+
+    /// Executes the transaction again in a fresh local environment with the recorded accounts, sysvars, features and compute budget,
+    /// and compares the resulting state of the accounts to the recorded one.
+    /// Panics if the transaction used native programs added with [crate::LocalEnvironmentBuilder::add_builtin_program], use [Self::replay_with] for those.
+    pub fn replay(&self) -> Replay {
+        self.replay_with(&mut LocalEnvironment::builder())
+    }
+
+    /// Like [Self::replay], but builds the environment from the given builder, which must contain the native programs
+    /// of the capture, see [Self::builtin_programs]:
+    /// ```ignore
+    /// let mut builder = LocalEnvironment::builder();
+    /// builder.add_builtin_program("level0", program_id, processor!(level0::processor::process_instruction));
+    /// let replay = capture.replay_with(&mut builder);
+    /// ```
+    pub fn replay_with(&self, builder: &mut LocalEnvironmentBuilder) -> Replay {
+        for program in &self.builtin_programs {
+            if !builder
+                .builtins
+                .iter()
+                .any(|builtin| builtin.id == *program)
+            {
+                panic!(
+                    "the capture uses the native program {}, which is not part of the capture. Add it with add_builtin_program and replay with replay_with",
+                    program
+                );
+            }
+        }
+        let feature_set = FeatureSet {
+            active: self.active_features.iter().map(|id| (*id, 0)).collect(),
+            inactive: Default::default(),
+        };
+        builder.config.fee_rate_governor = FeeRateGovernor::new(self.lamports_per_signature, 0);
+        builder.with_feature_set(&feature_set);
+        if let Some(units) = self.compute_max_units {
+            builder.set_compute_max_units(units);
+        }
+        for (pubkey, account) in &self.pre_accounts {
+            match account {
+                Some(account) => {
+                    builder.add_account(*pubkey, account.clone());
+                }
+                None => {
+                    builder.config.accounts.remove(pubkey);
+                }
+            }
+        }
+        let mut env = builder.build();
+
+        if self.slot > env.bank.slot() {
+            env.warp_to_slot(self.slot);
+        }
+        env.set_sysvar(&self.sysvars.clock);
+        env.set_sysvar(&self.sysvars.rent);
+        env.set_sysvar(&self.sysvars.epoch_schedule);
+        env.set_sysvar(&self.sysvars.slot_hashes);
+        env.set_sysvar(&self.sysvars.stake_history);
+        env.bank
+            .register_recent_blockhash(self.transaction.message.recent_blockhash());
+
+        let outcome = env.execute_versioned_transaction(self.transaction.clone());
+        let mismatches = self
+            .post_accounts
+            .iter()
+            .filter_map(|(pubkey, recorded)| {
+                let replayed = env.get_account(*pubkey);
+                (replayed != *recorded).then(|| AccountMismatch {
+                    pubkey: *pubkey,
+                    recorded: recorded.clone(),
+                    replayed,
+                })
+            })
+            .collect();
+        Replay {
+            status_matches: outcome.status == self.status,
+            outcome,
+            mismatches,
+        }
+    }
+}
+
+impl LocalEnvironment {
+    /// Records the state the transaction is executed on. None if the transaction can not be sanitized.
+    pub(crate) fn begin_capture(&self, tx: &VersionedTransaction) -> Option<TransactionCapture> {
+        let sanitized = SanitizedTransaction::try_create(
+            tx.clone(),
+            MessageHash::Compute,
+            None,
+            &*self.bank,
+            self.bank
+                .feature_set
+                .is_active(&feature_set::require_static_program_ids_in_transaction::ID),
+        )
+        .ok()?;
+        let mut pubkeys: Vec<Pubkey> = sanitized.message().account_keys().iter().copied().collect();
+        if let Some(lookups) = tx.message.address_table_lookups() {
+            pubkeys.extend(lookups.iter().map(|lookup| lookup.account_key));
+        }
+        // Upgradeable programs are executed from their program data account
+        for pubkey in pubkeys.clone() {
+            let account = match self.get_account(pubkey) {
+                Some(account) if account.owner == bpf_loader_upgradeable::ID => account,
+                _ => continue,
+            };
+            if let Ok(UpgradeableLoaderState::Program {
+                programdata_address,
+            }) = bincode::deserialize(&account.data)
+            {
+                pubkeys.push(programdata_address);
+            }
+        }
+        pubkeys.sort();
+        pubkeys.dedup();
+
+        Some(TransactionCapture {
+            version: CAPTURE_VERSION,
+            slot: self.bank.slot(),
+            transaction: tx.clone(),
+            lamports_per_signature: self
+                .bank
+                .get_lamports_per_signature_for_blockhash(tx.message.recent_blockhash())
+                .unwrap_or_default(),
+            sysvars: CapturedSysvars {
+                clock: self.bank.clock(),
+                rent: self.bank.rent_collector().rent,
+                epoch_schedule: *self.bank.epoch_schedule(),
+                slot_hashes: self.sysvar(),
+                stake_history: self.sysvar(),
+            },
+            active_features: self.bank.feature_set.active.keys().copied().collect(),
+            compute_max_units: self.compute_budget.map(|budget| budget.compute_unit_limit),
+            builtin_programs: self
+                .builtin_programs
+                .iter()
+                .filter(|program| pubkeys.contains(program))
+                .copied()
+                .collect(),
+            pre_accounts: self.accounts(&pubkeys),
+            post_accounts: vec![],
+            status: Ok(()),
+            logs: vec![],
+        })
+    }
+
+    /// Completes the capture with the result of the transaction and writes it into the capture directory.
+    pub(crate) fn finish_capture(
+        &self,
+        mut capture: TransactionCapture,
+        outcome: &ExecutionOutcome,
+    ) {
+        let pubkeys: Vec<Pubkey> = capture
+            .pre_accounts
+            .iter()
+            .map(|(pubkey, _)| *pubkey)
+            .collect();
+        capture.post_accounts = self.accounts(&pubkeys);
+        capture.status = outcome.status.clone();
+        capture.logs = outcome.logs.clone();
+
+        let dir = self.capture_dir.as_ref().expect("capturing is not enabled");
+        capture.save(dir.join(format!("{}.capture", capture.transaction.signatures[0])));
+    }
+
+    fn accounts(&self, pubkeys: &[Pubkey]) -> Vec<(Pubkey, Option<Account>)> {
+        pubkeys
+            .iter()
+            .map(|pubkey| (*pubkey, self.get_account(*pubkey)))
+            .collect()
+    }
+
+    fn sysvar<T: Sysvar + SysvarId>(&self) -> T {
+        self.bank
+            .get_account(&T::id())
+            .and_then(|account| from_account(&account))
+            .expect("sysvar does not exist")
+    }
+}
@@ -0,0 +1,241 @@
+use std::{collections::BTreeMap, fmt};
+
+use serde_json::Value;
+use solana_account_decoder::parse_account_data::{
+    parse_account_data, AccountAdditionalData, ParsedAccount,
+};
+use solana_program::{program_pack::Pack, pubkey::Pubkey};
+use solana_sdk::account::Account;
+
+/// The change of an account between two points of a PoC, as listed by [crate::LocalEnvironment::diff_since].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccountDiff {
+    pub pubkey: Pubkey,
+    /// None if the account did not exist.
+    pub before: Option<Account>,
+    /// None if the account does not exist anymore.
+    pub after: Option<Account>,
+    /// The data decoded with `solana-account-decoder`. None if the account did not exist or its data can not be decoded.
+    pub parsed_before: Option<ParsedAccount>,
+    pub parsed_after: Option<ParsedAccount>,
+}
+
+/// A range of bytes of the account data that changed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DataChange {
+    pub offset: usize,
+    /// The bytes before the change. Shorter than `after` if the account grew.
+    pub before: Vec<u8>,
+    /// The bytes after the change. Shorter than `before` if the account shrunk.
+    pub after: Vec<u8>,
+}
+
+/// A field of the decoded account data that changed. The path is made of the object keys and array indices leading to the field.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldChange {
+    pub path: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+impl AccountDiff {
+    /// The signed difference between the lamports after and before.
+    pub fn lamport_delta(&self) -> i128 {
+        let lamports = |account: &Option<Account>| account.as_ref().map_or(0, |acc| acc.lamports);
+        lamports(&self.after) as i128 - lamports(&self.before) as i128
+    }
+
+    /// The ranges of the raw account data that changed. A missing account counts as empty data.
+    pub fn data_changes(&self) -> Vec<DataChange> {
+        let data =
+            |account: &Option<Account>| account.as_ref().map_or(vec![], |acc| acc.data.clone());
+        let (before, after) = (data(&self.before), data(&self.after));
+
+        let mut changes: Vec<DataChange> = vec![];
+        let mut change_end = None;
+        for offset in 0..before.len().max(after.len()) {
+            if before.get(offset) == after.get(offset) {
+                continue;
+            }
+            let change = match changes.last_mut() {
+                Some(change) if change_end == Some(offset) => change,
+                _ => {
+                    changes.push(DataChange {
+                        offset,
+                        before: vec![],
+                        after: vec![],
+                    });
+                    changes.last_mut().unwrap()
+                }
+            };
+            change.before.extend(before.get(offset));
+            change.after.extend(after.get(offset));
+            change_end = Some(offset + 1);
+        }
+        changes
+    }
+
+    /// The fields of the decoded data that changed. None if neither side of the data can be decoded.
+    pub fn field_changes(&self) -> Option<Vec<FieldChange>> {
+        if self.parsed_before.is_none() && self.parsed_after.is_none() {
+            return None;
+        }
+        let fields = |parsed: &Option<ParsedAccount>| {
+            let mut fields = BTreeMap::new();
+            if let Some(parsed) = parsed {
+                flatten("", &parsed.parsed, &mut fields);
+            }
+            fields
+        };
+        let (mut before, after) = (fields(&self.parsed_before), fields(&self.parsed_after));
+
+        let mut changes = vec![];
+        for (path, after) in after {
+            let before = before.remove(&path);
+            if before.as_ref() != Some(&after) {
+                changes.push(FieldChange {
+                    path,
+                    before,
+                    after: Some(after),
+                });
+            }
+        }
+        changes.extend(before.into_iter().map(|(path, before)| FieldChange {
+            path,
+            before: Some(before),
+            after: None,
+        }));
+        changes.sort_by(|a, b| a.path.cmp(&b.path));
+        Some(changes)
+    }
+}
+
+impl fmt::Display for AccountDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (before, after) = match (&self.before, &self.after) {
+            (None, None) => return writeln!(f, "account {} unchanged", self.pubkey),
+            (None, Some(after)) => {
+                writeln!(f, "account {} created", self.pubkey)?;
+                (Account::default(), after.clone())
+            }
+            (Some(before), None) => {
+                writeln!(f, "account {} closed", self.pubkey)?;
+                (before.clone(), Account::default())
+            }
+            (Some(before), Some(after)) => {
+                writeln!(f, "account {} changed", self.pubkey)?;
+                (before.clone(), after.clone())
+            }
+        };
+
+        if before.lamports != after.lamports {
+            writeln!(
+                f,
+                "  lamports: {} -> {} ({:+})",
+                before.lamports,
+                after.lamports,
+                self.lamport_delta()
+            )?;
+        }
+        if before.owner != after.owner {
+            writeln!(f, "  owner: {} -> {}", before.owner, after.owner)?;
+        }
+        if before.executable != after.executable {
+            writeln!(
+                f,
+                "  executable: {} -> {}",
+                before.executable, after.executable
+            )?;
+        }
+        if before.data.len() != after.data.len() {
+            writeln!(
+                f,
+                "  data length: {} -> {}",
+                before.data.len(),
+                after.data.len()
+            )?;
+        }
+
+        if let Some(changes) = self.field_changes() {
+            let program = self
+                .parsed_after
+                .as_ref()
+                .or(self.parsed_before.as_ref())
+                .map(|parsed| parsed.program.as_str())
+                .unwrap_or_default();
+            let show =
+                |value: &Option<Value>| value.as_ref().map_or("-".to_string(), Value::to_string);
+            for change in changes {
+                writeln!(
+                    f,
+                    "  {} {}: {} -> {}",
+                    program,
+                    change.path,
+                    show(&change.before),
+                    show(&change.after)
+                )?;
+            }
+        } else {
+            for change in self.data_changes() {
+                writeln!(
+                    f,
+                    "  data[{}..{}]: {} -> {}",
+                    change.offset,
+                    change.offset + change.before.len().max(change.after.len()),
+                    hex::encode(&change.before),
+                    hex::encode(&change.after)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Decodes the account data with `solana-account-decoder`. Token accounts need their mint, which is looked up with `get_account`.
+pub(crate) fn parse_account(
+    pubkey: &Pubkey,
+    account: &Account,
+    get_account: impl Fn(&Pubkey) -> Option<Account>,
+) -> Option<ParsedAccount> {
+    let spl_token_decimals = if account.owner == spl_token::ID {
+        spl_token::state::Account::unpack(&account.data)
+            .ok()
+            .and_then(|token_account| get_account(&token_account.mint))
+            .and_then(|mint| spl_token::state::Mint::unpack(&mint.data).ok())
+            .map(|mint| mint.decimals)
+    } else {
+        None
+    };
+    parse_account_data(
+        pubkey,
+        &account.owner,
+        &account.data,
+        Some(AccountAdditionalData { spl_token_decimals }),
+    )
+    .ok()
+}
+
+fn flatten(path: &str, value: &Value, fields: &mut BTreeMap<String, Value>) {
+    let join = |key: &str| {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", path, key)
+        }
+    };
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object {
+                flatten(&join(key), value, fields);
+            }
+        }
+        Value::Array(array) => {
+            for (index, value) in array.iter().enumerate() {
+                flatten(&format!("{}[{}]", path, index), value, fields);
+            }
+        }
+        _ => {
+            fields.insert(path.to_string(), value.clone());
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! Searches for missing signer and writable checks by mutating the account flags of a successful transaction.
+//!
+//! [LocalEnvironment::check_account_flags] executes the instructions once for every signer flag, with the flag cleared and the
+//! signature removed, and once for every writable flag, with the flag toggled. A program that accepts an instruction without
+//! a signature it asked for doesn't check that the account signed.
+//!
+//! ```ignore
+//! let withdraw = level1::withdraw(wallet_program, authority.pubkey(), destination, amount);
+//! let report = env.check_account_flags(&[withdraw], &[&authority]);
+//! report.print();
+//! ```
+
+use std::fmt;
+
+use solana_program::{instruction::Instruction, message::Message, pubkey::Pubkey};
+use solana_sdk::signature::{Keypair, Signer};
+
+use crate::{variants, Environment, ExecutionOutcome, LocalEnvironment};
+
+/// How the flags of an account were changed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlagMutation {
+    /// The signer flag was cleared and the signature of the account removed from the transaction.
+    ClearSigner,
+    /// The account was passed read-only instead of writable.
+    ClearWritable,
+    /// The account was passed writable instead of read-only.
+    SetWritable,
+}
+
+impl fmt::Display for FlagMutation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FlagMutation::ClearSigner => write!(f, "without signature"),
+            FlagMutation::ClearWritable => write!(f, "read-only"),
+            FlagMutation::SetWritable => write!(f, "writable"),
+        }
+    }
+}
+
+/// The transaction with the flags of one account changed, executed by [LocalEnvironment::check_account_flags].
+#[derive(Clone, Debug)]
+pub struct FlagVariant {
+    /// The index of the instruction in the transaction.
+    pub instruction: usize,
+    /// The index of the account in the instruction.
+    pub account: usize,
+    pub pubkey: Pubkey,
+    pub mutation: FlagMutation,
+    pub outcome: ExecutionOutcome,
+}
+
+impl FlagVariant {
+    /// Returns whether the program accepted the changed flags.
+    pub fn accepted(&self) -> bool {
+        self.outcome.status.is_ok()
+    }
+}
+
+/// The result of [LocalEnvironment::check_account_flags].
+#[derive(Clone, Debug)]
+pub struct FlagReport {
+    /// The outcome of the unchanged transaction. If it failed, the rejected variants don't tell anything.
+    pub original: ExecutionOutcome,
+    pub variants: Vec<FlagVariant>,
+}
+
+impl FlagReport {
+    /// The variants the programs accepted.
+    pub fn accepted(&self) -> impl Iterator<Item = &FlagVariant> {
+        self.variants.iter().filter(|variant| variant.accepted())
+    }
+
+    /// The variants the programs accepted without a signature they asked for.
+    pub fn missing_signer_checks(&self) -> impl Iterator<Item = &FlagVariant> {
+        self.accepted()
+            .filter(|variant| variant.mutation == FlagMutation::ClearSigner)
+    }
+
+    /// Prints every variant. Accepted writable flags are harmless, but show which accounts an instruction doesn't write to.
+    /// FAIL means a program accepted an instruction without a signature.
+    pub fn print(&self) {
+        variants::print_header(
+            format_args!("ACCOUNT FLAGS: {} variants", self.variants.len()),
+            &self.original,
+        );
+        for variant in &self.variants {
+            variants::print_variant(
+                match variant.mutation {
+                    FlagMutation::ClearSigner => "FAIL",
+                    _ => "INFO",
+                },
+                format_args!(
+                    "instruction {}, account {} ({}) {}",
+                    variant.instruction, variant.account, variant.pubkey, variant.mutation
+                ),
+                &variant.outcome,
+            );
+        }
+        let missing = self.missing_signer_checks().count();
+        variants::print_result(
+            missing,
+            "every signature is checked",
+            format_args!("{} signatures are not checked", missing),
+        );
+    }
+}
+
+impl LocalEnvironment {
+    /// Executes the instructions with the signer and writable flags of every account changed, one at a time, see the [module docs](self).
+    /// Signatures that are no longer required are removed, like the unnecessary signatures [Environment::tx_with_instructions] warns about.
+    /// Changes that don't alter the transaction, e.g. because the account is passed to another instruction as signer too, are skipped.
+    ///
+    /// Every variant is executed on a snapshot of the current state, and the environment is rolled back to it afterwards.
+    /// Invariants are not checked for the variants.
+    pub fn check_account_flags(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> FlagReport {
+        let payer = self.payer().pubkey();
+        let original_message = Message::new(instructions, Some(&payer));
+
+        let mut variants = vec![];
+        for (instruction, ix) in instructions.iter().enumerate() {
+            for (account, meta) in ix.accounts.iter().enumerate() {
+                let mut mutations = vec![];
+                if meta.is_signer {
+                    mutations.push(FlagMutation::ClearSigner);
+                }
+                mutations.push(if meta.is_writable {
+                    FlagMutation::ClearWritable
+                } else {
+                    FlagMutation::SetWritable
+                });
+
+                for mutation in mutations {
+                    let mut mutated = instructions.to_vec();
+                    let mutated_meta = &mut mutated[instruction].accounts[account];
+                    match mutation {
+                        FlagMutation::ClearSigner => mutated_meta.is_signer = false,
+                        FlagMutation::ClearWritable => mutated_meta.is_writable = false,
+                        FlagMutation::SetWritable => mutated_meta.is_writable = true,
+                    }
+                    if Message::new(&mutated, Some(&payer)) != original_message {
+                        variants.push(((instruction, account, meta.pubkey, mutation), mutated));
+                    }
+                }
+            }
+        }
+
+        let (original, outcomes) =
+            self.run_variants(instructions, signers, variants, |_, (_, mutated)| {
+                Some((mutated.clone(), vec![]))
+            });
+        let variants = outcomes
+            .into_iter()
+            .map(
+                |(((instruction, account, pubkey, mutation), _), outcome)| FlagVariant {
+                    instruction,
+                    account,
+                    pubkey,
+                    mutation,
+                    outcome,
+                },
+            )
+            .collect();
+        FlagReport { original, variants }
+    }
+}
@@ -0,0 +1,625 @@
+//! Searches for exploits by executing random sequences of instructions.
+//!
+//! A [Fuzzer] knows how to build the instructions of a program, the keys an attacker holds or knows, and the invariants of the program.
+//! It executes random sequences of these instructions, with mutated accounts, signer flags and amounts, on forks of a [LocalEnvironment]
+//! in parallel. Every sequence that breaks an invariant or makes an attacker profit is shrunk to a minimal reproducer.
+//!
+//! ```ignore
+//! let mut fuzzer = Fuzzer::new();
+//! fuzzer
+//!     .add_instruction("withdraw", move |input| {
+//!         level3::withdraw(tip_program, input.key(), input.key(), input.signer(), input.amount())
+//!     })
+//!     .add_signer(&hacker)
+//!     .add_addresses(&[vault_address, pool_address])
+//!     .add_attacker(hacker.pubkey())
+//!     .set_invariants(move |env| vec![Invariant::program_lamports_never_decrease(env, tip_program, &[])]);
+//! let report = fuzzer.run(&mut env);
+//! report.print();
+//! ```
+
+use std::{
+    collections::BTreeSet,
+    fmt,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use rand::{prelude::StdRng, Rng, SeedableRng};
+use solana_program::{instruction::Instruction, message::Message, pubkey::Pubkey};
+use solana_runtime::bank::Bank;
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+
+use crate::{
+    assert::{BalanceTracker, Invariant, Report},
+    clone_keypair, Environment, LocalEnvironment, Snapshot,
+};
+
+type Build = Box<dyn Fn(&mut Input) -> Instruction + Send + Sync>;
+type Invariants = Box<dyn Fn(&LocalEnvironment) -> Vec<Invariant> + Send + Sync>;
+
+/// Amounts that often hit edge cases, drawn by [Input::amount] besides random ones.
+const INTERESTING_AMOUNTS: [u64; 8] = [
+    0,
+    1,
+    2,
+    1_000_000_000,
+    u64::MAX / 2,
+    u64::MAX / 2 + 1,
+    u64::MAX - 1,
+    u64::MAX,
+];
+
+/// The number of times a failing sequence is executed at most while shrinking it.
+const MAX_SHRINK_RUNS: usize = 1000;
+
+/// The source of the keys and amounts of an instruction built by the [Fuzzer].
+///
+/// Every draw is recorded, so the instruction can be built again and shrunk. Shrinking moves draws towards 0,
+/// which is the first key and the amount 0.
+pub struct Input<'a> {
+    rng: Option<&'a mut StdRng>,
+    replayed: &'a [u64],
+    choices: Vec<u64>,
+    keys: &'a [Pubkey],
+    signers: usize,
+    mutation_rate: u32,
+}
+
+impl<'a> Input<'a> {
+    fn draw(&mut self, generate: impl FnOnce(&mut StdRng) -> u64) -> u64 {
+        let choice = match &mut self.rng {
+            Some(rng) => generate(rng),
+            None => self.replayed.get(self.choices.len()).copied().unwrap_or(0),
+        };
+        self.choices.push(choice);
+        choice
+    }
+
+    fn index(&mut self, len: usize) -> usize {
+        (self.draw(|rng| rng.gen_range(0, len as u64)) % len as u64) as usize
+    }
+
+    /// Any of the keys the fuzzer knows, signers first.
+    pub fn key(&mut self) -> Pubkey {
+        let index = self.index(self.keys.len());
+        self.keys[index]
+    }
+
+    /// One of the keys the attacker can sign with.
+    pub fn signer(&mut self) -> Pubkey {
+        let index = self.index(self.signers);
+        self.keys[index]
+    }
+
+    /// An amount of lamports or tokens. Edge cases like 0 and `u64::MAX` are drawn more often than others.
+    pub fn amount(&mut self) -> u64 {
+        self.draw(|rng| {
+            if rng.gen_bool(0.5) {
+                INTERESTING_AMOUNTS[rng.gen_range(0, INTERESTING_AMOUNTS.len())]
+            } else if rng.gen_bool(0.5) {
+                rng.gen_range(0, 1 << 32)
+            } else {
+                rng.gen()
+            }
+        })
+    }
+
+    /// A number in `0..n`, e.g. to choose an instruction variant. Panics if `n` is zero.
+    pub fn below(&mut self, n: u64) -> u64 {
+        assert!(n > 0, "Input::below needs a non-empty range, but n is 0");
+        self.draw(|rng| rng.gen_range(0, n)) % n
+    }
+
+    /// Replaces accounts and flips the signer and writable flags of the accounts of the built instruction.
+    fn mutate(&mut self, instruction: &mut Instruction) {
+        let mutation_rate = self.mutation_rate;
+        for index in 0..instruction.accounts.len() {
+            let mutation = self.draw(|rng| {
+                if rng.gen_ratio(1, mutation_rate) {
+                    rng.gen_range(1, 4)
+                } else {
+                    0
+                }
+            });
+            let meta = &mut instruction.accounts[index];
+            match mutation % 4 {
+                1 => meta.pubkey = self.key(),
+                2 => meta.is_signer = !meta.is_signer,
+                3 => meta.is_writable = !meta.is_writable,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// One instruction of a sequence, as the choices its builder made.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Call {
+    builder: usize,
+    choices: Vec<u64>,
+}
+
+/// An instruction executed by the fuzzer, in its own transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FuzzCall {
+    /// The name the builder was added with.
+    pub name: String,
+    pub instruction: Instruction,
+    /// The keys that signed the transaction besides the payer.
+    pub signers: Vec<Pubkey>,
+}
+
+impl fmt::Display for FuzzCall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} ({})", self.name, self.instruction.program_id)?;
+        for (index, meta) in self.instruction.accounts.iter().enumerate() {
+            writeln!(
+                f,
+                "    Account {}: {}{}- {}",
+                index,
+                if meta.is_signer { "s" } else { "-" },
+                if meta.is_writable { "w" } else { "r" },
+                meta.pubkey
+            )?;
+        }
+        writeln!(f, "    Data: {:?}", self.instruction.data)
+    }
+}
+
+/// A sequence of instructions that broke an invariant or made an attacker profit, shrunk to a minimal reproducer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    pub calls: Vec<FuzzCall>,
+    /// The report of the reproducer, executed on the environment the fuzzer started with.
+    pub report: Report,
+    /// The length of the sequence before it was shrunk.
+    pub original_length: usize,
+}
+
+impl Finding {
+    /// The names of the broken checks, which tell findings apart.
+    pub fn broken(&self) -> BTreeSet<String> {
+        broken(&self.report)
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "FINDING: {}",
+            self.broken().into_iter().collect::<Vec<_>>().join(", ")
+        )?;
+        writeln!(
+            f,
+            "  {} instructions, shrunk from {}",
+            self.calls.len(),
+            self.original_length
+        )?;
+        for (index, call) in self.calls.iter().enumerate() {
+            write!(f, "  {}. {}", index + 1, call)?;
+        }
+        Ok(())
+    }
+}
+
+/// The result of [Fuzzer::run].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FuzzReport {
+    /// The number of sequences executed, without the ones executed while shrinking.
+    pub sequences: u64,
+    /// The number of transactions executed, including the ones executed while shrinking.
+    pub transactions: u64,
+    pub duration: Duration,
+    /// The shortest reproducer of every distinct set of broken checks.
+    pub findings: Vec<Finding>,
+}
+
+impl FuzzReport {
+    /// Prints all findings.
+    pub fn print(&self) {
+        println!(
+            "FUZZING: {} sequences, {} transactions in {:?}",
+            self.sequences, self.transactions, self.duration
+        );
+        for finding in &self.findings {
+            print!("{}", finding);
+        }
+        if self.findings.is_empty() {
+            println!("RESULT: nothing found");
+        } else {
+            println!("RESULT: {} findings", self.findings.len());
+        }
+    }
+}
+
+/// Executes random sequences of instructions against a [LocalEnvironment], see the [module docs](self).
+pub struct Fuzzer {
+    builders: Vec<(String, Build)>,
+    signers: Vec<Keypair>,
+    addresses: Vec<Pubkey>,
+    attackers: Vec<Pubkey>,
+    invariants: Invariants,
+    threads: usize,
+    iterations: u64,
+    max_sequence_length: usize,
+    mutation_rate: u32,
+    seed: u64,
+}
+
+impl Default for Fuzzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Fuzzer {
+    /// A fuzzer without instructions, keys and invariants. It runs 1000 sequences of up to 5 instructions on every available core.
+    pub fn new() -> Self {
+        Fuzzer {
+            builders: vec![],
+            signers: vec![],
+            addresses: vec![],
+            attackers: vec![],
+            invariants: Box::new(|_| vec![]),
+            threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+            iterations: 1000,
+            max_sequence_length: 5,
+            mutation_rate: 8,
+            seed: 0,
+        }
+    }
+
+    /// Adds an instruction to the sequences. The builder draws the keys and amounts of the instruction from the input, like
+    /// `|input| level3::tip(tip_program, input.key(), input.key(), input.signer(), input.amount())`.
+    pub fn add_instruction<F>(&mut self, name: &str, build: F) -> &mut Self
+    where
+        F: Fn(&mut Input) -> Instruction + Send + Sync + 'static,
+    {
+        self.builders.push((name.to_string(), Box::new(build)));
+        self
+    }
+
+    /// Adds a key the attacker can sign with.
+    pub fn add_signer(&mut self, keypair: &Keypair) -> &mut Self {
+        self.signers.push(clone_keypair(keypair));
+        self
+    }
+
+    /// Adds keys the attacker can sign with, e.g. a range of [crate::keypair]s.
+    pub fn add_signers(&mut self, keypairs: &[&Keypair]) -> &mut Self {
+        for keypair in keypairs {
+            self.add_signer(keypair);
+        }
+        self
+    }
+
+    /// Adds keys the attacker knows, but can't sign with, like the accounts of the victims and programs.
+    pub fn add_addresses(&mut self, addresses: &[Pubkey]) -> &mut Self {
+        self.addresses.extend_from_slice(addresses);
+        self
+    }
+
+    /// Adds an attacker whose profit counts as a finding, like in [crate::assert::Report::exploit_succeeded].
+    pub fn add_attacker(&mut self, attacker: Pubkey) -> &mut Self {
+        self.attackers.push(attacker);
+        self
+    }
+
+    /// Sets the invariants checked after every transaction. They are created anew for every sequence,
+    /// as invariants like [Invariant::program_lamports_never_decrease] record the state they start from.
+    pub fn set_invariants<F>(&mut self, invariants: F) -> &mut Self
+    where
+        F: Fn(&LocalEnvironment) -> Vec<Invariant> + Send + Sync + 'static,
+    {
+        self.invariants = Box::new(invariants);
+        self
+    }
+
+    /// Sets the number of threads, each executing sequences on its own fork of the environment.
+    pub fn set_threads(&mut self, threads: usize) -> &mut Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Sets the number of sequences executed by every thread.
+    pub fn set_iterations(&mut self, iterations: u64) -> &mut Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Sets the maximum number of instructions in a sequence.
+    pub fn set_max_sequence_length(&mut self, max_sequence_length: usize) -> &mut Self {
+        self.max_sequence_length = max_sequence_length.max(1);
+        self
+    }
+
+    /// Mutates every account of a built instruction with a chance of 1 in `rate`.
+    pub fn set_mutation_rate(&mut self, rate: u32) -> &mut Self {
+        self.mutation_rate = rate.max(1);
+        self
+    }
+
+    /// Sets the seed of the random sequences. Together with [LocalEnvironment::seeded_builder], a run can be repeated exactly.
+    pub fn set_seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Fuzzes the current state of the environment. The environment is restored to it afterwards.
+    pub fn run(&self, env: &mut LocalEnvironment) -> FuzzReport {
+        assert!(!self.builders.is_empty(), "the fuzzer has no instructions");
+        assert!(!self.signers.is_empty(), "the fuzzer has no signers");
+        let start = Instant::now();
+        let base = env.snapshot();
+        // Every worker executes in its own range of slots, as forks must not share a slot
+        let slots_per_worker = self.max_sequence_length as u64 + 2;
+        let first_slot = env.bank.slot() + 1;
+
+        let results = thread::scope(|scope| {
+            let workers = (0..self.threads)
+                .map(|index| {
+                    let base = &base;
+                    let payer = clone_keypair(&env.faucet);
+                    let compute_budget = env.compute_budget;
+                    let builtin_programs = env.builtin_programs.clone();
+                    let tmpdir = env.tmpdir.clone();
+                    let slot = first_slot + index as u64 * slots_per_worker;
+                    thread::Builder::new()
+                        .name(format!("fuzz-{}", index))
+                        // Native programs in debug builds need more than the default stack
+                        .stack_size(64 * 1024 * 1024)
+                        .spawn_scoped(scope, move || {
+                            let env = LocalEnvironment {
+                                bank: Arc::new(Bank::new_from_parent(
+                                    &base.bank,
+                                    base.bank.collector_id(),
+                                    slot,
+                                )),
+                                faucet: payer,
+                                compute_budget,
+                                capture_dir: None,
+                                invariants: vec![],
+                                profiling: false,
+                                builtin_programs,
+                                tmpdir,
+                            };
+                            Worker::new(self, env).run(self.seed.wrapping_add(index as u64), base)
+                        })
+                        .expect("couldn't spawn fuzzing thread")
+                })
+                .collect::<Vec<_>>();
+            workers
+                .into_iter()
+                .map(|worker| worker.join().expect("fuzzing thread panicked"))
+                .collect::<Vec<_>>()
+        });
+        env.restore(&base);
+
+        let mut report = FuzzReport {
+            sequences: 0,
+            transactions: 0,
+            duration: Duration::default(),
+            findings: vec![],
+        };
+        for (sequences, transactions, findings) in results {
+            report.sequences += sequences;
+            report.transactions += transactions;
+            for finding in findings {
+                match report
+                    .findings
+                    .iter_mut()
+                    .find(|known| known.broken() == finding.broken())
+                {
+                    Some(known) if known.calls.len() > finding.calls.len() => *known = finding,
+                    Some(_) => {}
+                    None => report.findings.push(finding),
+                }
+            }
+        }
+        report.duration = start.elapsed();
+        report
+    }
+}
+
+/// The names of the checks broken in the report.
+fn broken(report: &Report) -> BTreeSet<String> {
+    report
+        .changes
+        .iter()
+        .filter(|change| change.is_profit())
+        .map(|change| format!("attacker {} profits", change.owner))
+        .chain(
+            report
+                .invariants
+                .iter()
+                .filter(|invariant| !invariant.violations.is_empty())
+                .map(|invariant| invariant.name.clone()),
+        )
+        .collect()
+}
+
+/// Executes sequences on a fork of the environment.
+struct Worker<'a> {
+    fuzzer: &'a Fuzzer,
+    env: LocalEnvironment,
+    /// The signers first, then the other addresses.
+    keys: Vec<Pubkey>,
+    transactions: u64,
+}
+
+impl<'a> Worker<'a> {
+    fn new(fuzzer: &'a Fuzzer, env: LocalEnvironment) -> Self {
+        let keys = fuzzer
+            .signers
+            .iter()
+            .map(|signer| signer.pubkey())
+            .chain(fuzzer.addresses.iter().copied())
+            .collect();
+        Worker {
+            fuzzer,
+            env,
+            keys,
+            transactions: 0,
+        }
+    }
+
+    /// Executes random sequences. Returns the number of sequences and transactions, and the shrunk findings.
+    fn run(mut self, seed: u64, base: &Snapshot) -> (u64, u64, Vec<Finding>) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let initial = self.env.snapshot();
+        let mut findings: Vec<Finding> = vec![];
+        for _ in 0..self.fuzzer.iterations {
+            let length = rng.gen_range(1, self.fuzzer.max_sequence_length + 1);
+            let mut calls = (0..length)
+                .map(|_| Call {
+                    builder: rng.gen_range(0, self.fuzzer.builders.len()),
+                    choices: vec![],
+                })
+                .collect::<Vec<_>>();
+            let (_, report) = self.execute(&initial, &mut calls, Some(&mut rng));
+            let broken = broken(&report);
+            if broken.is_empty() || findings.iter().any(|finding| finding.broken() == broken) {
+                continue;
+            }
+            let calls = self.shrink(&initial, calls, &broken);
+            let (calls, report) = self.execute(&initial, &mut calls.clone(), None);
+            // Only report what can be reproduced
+            if self::broken(&report) != broken {
+                continue;
+            }
+            findings.push(Finding {
+                calls,
+                report,
+                original_length: length,
+            });
+        }
+        // Purge the fork, so its slots can be reused by the environment
+        self.env.purge_banks_since(base);
+        (self.fuzzer.iterations, self.transactions, findings)
+    }
+
+    /// Executes the calls from the initial state, generating their choices if an rng is given.
+    /// The choices of the calls are replaced by the ones actually drawn.
+    fn execute(
+        &mut self,
+        initial: &Snapshot,
+        calls: &mut [Call],
+        mut rng: Option<&mut StdRng>,
+    ) -> (Vec<FuzzCall>, Report) {
+        self.env.restore(initial);
+        self.env.invariants = (self.fuzzer.invariants)(&self.env);
+        let tracker = BalanceTracker::new(&self.env, &self.fuzzer.attackers);
+        let payer = self.env.payer();
+
+        let mut executed = vec![];
+        for (index, call) in calls.iter_mut().enumerate() {
+            if index > 0 {
+                // Every transaction gets a new blockhash, so repeated instructions are no duplicate transactions
+                let slot = self.env.bank.slot() + 1;
+                self.env.warp_to_slot(slot);
+                self.env.advance_blockhash();
+            }
+            let (name, build) = &self.fuzzer.builders[call.builder];
+            let mut input = Input {
+                rng: rng.as_deref_mut(),
+                replayed: &call.choices,
+                choices: vec![],
+                keys: &self.keys,
+                signers: self.fuzzer.signers.len(),
+                mutation_rate: self.fuzzer.mutation_rate,
+            };
+            let mut instruction = build(&mut input);
+            input.mutate(&mut instruction);
+            call.choices = input.choices;
+
+            // The attacker can only sign with its own keys
+            let signers = self
+                .fuzzer
+                .signers
+                .iter()
+                .filter(|signer| signer.pubkey() != payer.pubkey())
+                .filter(|signer| {
+                    instruction
+                        .accounts
+                        .iter()
+                        .any(|meta| meta.is_signer && meta.pubkey == signer.pubkey())
+                })
+                .collect::<Vec<_>>();
+            for meta in &mut instruction.accounts {
+                meta.is_signer &= meta.pubkey == payer.pubkey()
+                    || signers.iter().any(|signer| signer.pubkey() == meta.pubkey);
+            }
+            let mut signing = vec![&payer];
+            signing.extend_from_slice(&signers);
+            let message = Message::new(&[instruction.clone()], Some(&payer.pubkey()));
+            let tx = Transaction::new(&signing, message, self.env.get_latest_blockhash());
+            self.env.execute_transaction(tx);
+            self.transactions += 1;
+
+            executed.push(FuzzCall {
+                name: name.clone(),
+                instruction,
+                signers: signers.iter().map(|signer| signer.pubkey()).collect(),
+            });
+        }
+        (executed, self.env.report(&tracker))
+    }
+
+    /// Removes calls and moves choices towards 0, as long as the sequence still breaks the same checks.
+    fn shrink(
+        &mut self,
+        initial: &Snapshot,
+        mut calls: Vec<Call>,
+        broken_checks: &BTreeSet<String>,
+    ) -> Vec<Call> {
+        let mut runs = 0;
+        let still_broken = |worker: &mut Self, candidate: &mut Vec<Call>, runs: &mut usize| {
+            *runs += 1;
+            let (_, report) = worker.execute(initial, candidate, None);
+            &broken(&report) == broken_checks
+        };
+
+        let mut shrunk = true;
+        while shrunk && runs < MAX_SHRINK_RUNS {
+            shrunk = false;
+            let mut index = 0;
+            while index < calls.len() && runs < MAX_SHRINK_RUNS {
+                let mut candidate = calls.clone();
+                candidate.remove(index);
+                if !candidate.is_empty() && still_broken(self, &mut candidate, &mut runs) {
+                    calls = candidate;
+                    shrunk = true;
+                } else {
+                    index += 1;
+                }
+            }
+            for call in 0..calls.len() {
+                // A replay drops the choices that are no longer drawn, so the number of choices can change
+                let mut choice = 0;
+                while choice < calls[call].choices.len() && runs < MAX_SHRINK_RUNS {
+                    let value = calls[call].choices[choice];
+                    for smaller in [0, value / 2, value.saturating_sub(1)] {
+                        if smaller >= value {
+                            continue;
+                        }
+                        let mut candidate = calls.clone();
+                        candidate[call].choices[choice] = smaller;
+                        if still_broken(self, &mut candidate, &mut runs) {
+                            calls = candidate;
+                            shrunk = true;
+                            break;
+                        }
+                    }
+                    choice += 1;
+                }
+            }
+        }
+        calls
+    }
+}
@@ -1,35 +1,56 @@
-use crate::solana_sdk::clock::UnixTimestamp;
-use std::convert::TryFrom;
+use crate::solana_sdk::clock::{Clock, Epoch, Slot, UnixTimestamp};
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeSet, HashMap, HashSet},
     convert::TryInto,
-    path::Path,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
     time::{SystemTime, UNIX_EPOCH},
 };
 
 use borsh::BorshDeserialize;
 use bpf_loader_upgradeable::UpgradeableLoaderState;
 use itertools::izip;
-use rand::{prelude::StdRng, rngs::OsRng, SeedableRng};
+use rand::{prelude::StdRng, rngs::OsRng, CryptoRng, RngCore, SeedableRng};
 use serde::de::DeserializeOwned;
+use serde_json::json;
 use sha2::{Digest, Sha256};
+use solana_account_decoder::{UiAccount, UiAccountEncoding};
+use solana_address_lookup_table_program::state::{AddressLookupTable, LookupTableMeta};
 use solana_bpf_loader_program::{
     solana_bpf_loader_deprecated_program, solana_bpf_loader_program,
     solana_bpf_loader_upgradeable_program,
 };
-use solana_cli_output::display::println_transaction;
-use solana_client::{rpc_client::RpcClient, rpc_config::RpcTransactionConfig};
+use solana_client::{
+    client_error::ClientErrorKind,
+    rpc_client::RpcClient,
+    rpc_config::{
+        RpcSendTransactionConfig, RpcSimulateTransactionAccountsConfig,
+        RpcSimulateTransactionConfig, RpcTransactionConfig,
+    },
+    rpc_request::{RpcError, RpcRequest, RpcResponseErrorData},
+    rpc_response::{RpcKeyedAccount, RpcSimulateTransactionResult},
+};
 use solana_program::{
+    address_lookup_table_account::AddressLookupTableAccount,
     bpf_loader, bpf_loader_upgradeable,
-    hash::Hash,
+    hash::{hashv, Hash},
     instruction::Instruction,
     loader_instruction,
-    message::{v0::LoadedAddresses, Message, SanitizedMessage},
+    message::{v0, Message, VersionedMessage},
+    native_token::sol_to_lamports,
     program_option::COption,
     program_pack::Pack,
     pubkey::Pubkey,
+    slot_hashes::SlotHashes,
     system_instruction, system_program,
-    sysvar::{self, rent},
+    sysvar::{self, rent, rent::Rent, slot_hashes, Sysvar, SysvarId},
+};
+use solana_program_runtime::{
+    compute_budget::ComputeBudget, invoke_context::ProcessInstructionWithContext,
+    timings::ExecuteTimings,
 };
 use solana_runtime::{
     accounts_db::AccountShrinkThreshold,
@@ -42,45 +63,92 @@ use solana_runtime::{
     genesis_utils,
 };
 use solana_sdk::{
-    account::{Account, AccountSharedData},
+    account::{self, Account, AccountSharedData, ReadableAccount},
     commitment_config::CommitmentConfig,
+    feature_set::{self, FeatureSet},
     genesis_config::GenesisConfig,
-    packet,
+    native_loader, packet,
     signature::Keypair,
-    signature::Signer,
+    signature::{Signature, Signer},
     system_transaction,
-    transaction::{Transaction, VersionedTransaction},
+    transaction::{MessageHash, SanitizedTransaction, Transaction, VersionedTransaction},
+    transaction_context::TransactionReturnData,
 };
+use solana_stake_program::stake_state;
 use solana_transaction_status::{
     token_balances, ConfirmedTransactionWithStatusMeta, EncodedConfirmedTransactionWithStatusMeta,
     InnerInstructions, TransactionStatusMeta, TransactionTokenBalance, TransactionWithStatusMeta,
     UiTransactionEncoding, VersionedTransactionWithStatusMeta,
 };
+use solana_vote_program::vote_state;
 use spl_associated_token_account::get_associated_token_address;
+use tempfile::TempDir;
 
 pub use bincode;
 pub use borsh;
 pub use serde;
 pub use solana_client;
 pub use solana_program;
+pub use solana_program_runtime;
 pub use solana_sdk;
 pub use solana_transaction_status;
 pub use spl_associated_token_account;
 pub use spl_memo;
 pub use spl_token;
 
+pub mod assert;
+mod builtin;
+mod capture;
+mod diff;
+pub mod flags;
+pub mod fuzz;
 mod keys;
+mod outcome;
+mod profile;
 mod programs;
+pub mod registry;
+pub mod substitute;
+mod trace;
+mod variants;
+
+use assert::{BalanceTracker, Invariant, InvariantResult, Report};
+pub use builtin::builtin_process_instruction;
+pub use capture::{AccountMismatch, CapturedSysvars, Replay, TransactionCapture, CAPTURE_VERSION};
+pub use diff::{AccountDiff, DataChange, FieldChange};
+pub use outcome::{
+    ExecutionOutcome, LamportChange, SimulationOutcome, TokenChange, TransactionFailure,
+};
+pub use profile::{ExecutionProfile, InvocationProfile};
+use registry::println_transaction;
+pub use trace::{ExecutionTrace, InvocationTrace, TraceStep};
+
+/// The creation time of the genesis of environments built by [LocalEnvironment::seeded_builder], 2022-07-01.
+pub const SEEDED_CREATION_TIME: UnixTimestamp = 1_656_633_600;
 
 /// A generic Environment trait. Provides the possibility of writing generic exploits that work both remote and local, for easy debugging.
 pub trait Environment {
     /// Returns the keypair used to pay for all transactions. All transaction fees and rent costs are payed for by this keypair.
     fn payer(&self) -> Keypair;
     /// Executes the batch of transactions in the right order and waits for them to be confirmed. The execution results are returned.
-    fn execute_transaction(
+    fn execute_transaction(&mut self, txs: Transaction) -> ExecutionOutcome {
+        self.execute_versioned_transaction(txs.into())
+    }
+    /// Executes the transaction and waits for it to be confirmed. Returns the error and logs if it failed, including failures during preflight simulation.
+    fn try_execute_transaction(
+        &mut self,
+        tx: Transaction,
+    ) -> Result<ExecutionOutcome, TransactionFailure> {
+        self.try_execute_versioned_transaction(tx.into())
+    }
+    /// Executes a legacy or v0 transaction and waits for it to be confirmed. The execution results are returned.
+    fn execute_versioned_transaction(&mut self, tx: VersionedTransaction) -> ExecutionOutcome;
+    /// Executes a legacy or v0 transaction and waits for it to be confirmed. Returns the error and logs if it failed.
+    fn try_execute_versioned_transaction(
         &mut self,
-        txs: Transaction,
-    ) -> EncodedConfirmedTransactionWithStatusMeta;
+        tx: VersionedTransaction,
+    ) -> Result<ExecutionOutcome, TransactionFailure>;
+    /// Simulates the transaction without committing any of its changes. Signatures are not verified.
+    fn simulate_transaction(&self, tx: Transaction) -> SimulationOutcome;
     /// Fetch a recent blockhash, for construction of transactions.
     #[deprecated(since = "0.2.0", note = "Please use `get_latest_blockhash()` instead")]
     fn get_recent_blockhash(&self) -> Hash {
@@ -93,6 +161,19 @@ pub trait Environment {
     /// Fetch an account. None if the account does not exist.
     fn get_account(&self, pubkey: Pubkey) -> Option<Account>;
 
+    /// Fetch an address lookup table, for construction of v0 transactions. None if the account does not exist or is no lookup table.
+    fn get_address_lookup_table(&self, pubkey: Pubkey) -> Option<AddressLookupTableAccount> {
+        let account = self.get_account(pubkey)?;
+        if account.owner != solana_address_lookup_table_program::ID {
+            return None;
+        }
+        let table = AddressLookupTable::deserialize(&account.data).ok()?;
+        Some(AddressLookupTableAccount {
+            key: pubkey,
+            addresses: table.addresses.to_vec(),
+        })
+    }
+
     /// Assemble the given instructions into a transaction and sign it. All transactions constructed by this method are signed and payed for by the payer.
     fn tx_with_instructions(
         &self,
@@ -105,24 +186,49 @@ pub trait Environment {
 
         let message = Message::new(instructions, Some(&self.payer().pubkey()));
         let num_sigs: usize = message.header.num_required_signatures.into();
-        let required_sigs = message.account_keys[..num_sigs]
-            .into_iter()
-            .copied()
-            .collect::<HashSet<_>>();
-        let provided_sigs = signer_vec
-            .iter()
-            .map(|x| x.pubkey())
-            .collect::<HashSet<_>>();
+        print_signature_mismatches(&message.account_keys[..num_sigs], &signer_vec);
 
-        for key in required_sigs.difference(&provided_sigs) {
-            println!("missing signature from {}", key.to_string());
-        }
+        Transaction::new(&signer_vec, message, self.get_latest_blockhash())
+    }
 
-        for key in provided_sigs.difference(&required_sigs) {
-            println!("unnecessary signature from {}", key.to_string());
-        }
+    /// Assemble the given instructions into a v0 transaction and sign it. Accounts contained in the given lookup tables are loaded through them.
+    /// All transactions constructed by this method are signed and payed for by the payer.
+    fn versioned_tx_with_instructions(
+        &self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+        address_lookup_tables: &[AddressLookupTableAccount],
+    ) -> VersionedTransaction {
+        let payer = self.payer();
+        let mut signer_vec = vec![&payer];
+        signer_vec.extend_from_slice(signers);
 
-        Transaction::new(&signer_vec, message, self.get_latest_blockhash())
+        let message = v0::Message::try_compile(
+            &payer.pubkey(),
+            instructions,
+            address_lookup_tables,
+            self.get_latest_blockhash(),
+        )
+        .expect("Failed to compile v0 message");
+        let num_sigs: usize = message.header.num_required_signatures.into();
+        print_signature_mismatches(&message.account_keys[..num_sigs], &signer_vec);
+
+        let message = VersionedMessage::V0(message);
+        let message_data = message.serialize();
+        let signatures = message.static_account_keys()[..num_sigs]
+            .iter()
+            .map(|key| {
+                signer_vec
+                    .iter()
+                    .find(|signer| signer.pubkey() == *key)
+                    .unwrap_or_else(|| panic!("missing signature from {}", key))
+                    .sign_message(&message_data)
+            })
+            .collect();
+        VersionedTransaction {
+            signatures,
+            message,
+        }
     }
 
     /// Assemble the given instructions into a transaction and sign it. All transactions executed by this method are signed and payed for by the payer.
@@ -130,18 +236,50 @@ pub trait Environment {
         &mut self,
         instructions: &[Instruction],
         signers: &[&Keypair],
-    ) -> EncodedConfirmedTransactionWithStatusMeta {
+    ) -> ExecutionOutcome {
         let tx = self.tx_with_instructions(instructions, signers);
         return self.execute_transaction(tx);
     }
 
+    /// Assemble the given instructions into a v0 transaction using the given lookup tables and sign it. All transactions executed by this method are signed and payed for by the payer.
+    fn execute_as_versioned_transaction(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+        address_lookup_tables: &[AddressLookupTableAccount],
+    ) -> ExecutionOutcome {
+        let tx = self.versioned_tx_with_instructions(instructions, signers, address_lookup_tables);
+        self.execute_versioned_transaction(tx)
+    }
+
+    /// Assemble the given instructions into a transaction and sign it. All transactions executed by this method are signed and payed for by the payer.
+    /// Returns the error and logs if the transaction failed.
+    fn try_execute_as_transaction(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<ExecutionOutcome, TransactionFailure> {
+        let tx = self.tx_with_instructions(instructions, signers);
+        self.try_execute_transaction(tx)
+    }
+
+    /// Assemble the given instructions into a transaction and sign it, then simulate it. Nothing of the simulation is committed.
+    fn simulate_as_transaction(
+        &self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> SimulationOutcome {
+        let tx = self.tx_with_instructions(instructions, signers);
+        self.simulate_transaction(tx)
+    }
+
     /// Assemble the given instructions into a transaction and sign it. All transactions executed by this method are signed and payed for by the payer.
     /// Prints the transaction before sending it.
     fn execute_as_transaction_debug(
         &mut self,
         instructions: &[Instruction],
         signers: &[&Keypair],
-    ) -> EncodedConfirmedTransactionWithStatusMeta {
+    ) -> ExecutionOutcome {
         let tx = self.tx_with_instructions(instructions, signers);
         println!("{:#?}", &tx);
         return self.execute_transaction(tx);
@@ -304,10 +442,7 @@ pub trait Environment {
     /// The keypair is derived from the file contents.
     fn deploy_program<P: AsRef<Path>>(&mut self, program_path: P) -> Pubkey {
         let data = std::fs::read(program_path).unwrap();
-        let mut hash = Sha256::default();
-        hash.update(&data);
-        let mut rng = StdRng::from_seed(hash.finalize()[..].try_into().unwrap());
-        let keypair = Keypair::generate(&mut rng);
+        let keypair = program_keypair(&data);
 
         if self.get_account(keypair.pubkey()).is_none() {
             self.create_account_with_data(&keypair, data);
@@ -324,6 +459,110 @@ pub trait Environment {
         keypair.pubkey()
     }
 
+    /// Create a buffer of the upgradeable bpf loader and write the program at the given path to it. The buffer is owned by the given authority.
+    /// The buffer address is derived from the program, the authority and the number of buffers this process created before,
+    /// so a PoC uses the same buffers on every run, and never the same buffer twice.
+    fn create_program_buffer<P: AsRef<Path>>(
+        &mut self,
+        program_path: P,
+        authority: &Keypair,
+    ) -> Pubkey {
+        let data = std::fs::read(program_path).unwrap();
+        let buffer = loop {
+            let buffer = buffer_keypair(&data, &authority.pubkey());
+            // Buffers left over from an earlier run against the same cluster can't be created again
+            if self.get_account(buffer.pubkey()).is_none() {
+                break buffer;
+            }
+        };
+        let buffer_len = UpgradeableLoaderState::size_of_buffer(data.len());
+        self.execute_as_transaction(
+            &bpf_loader_upgradeable::create_buffer(
+                &self.payer().pubkey(),
+                &buffer.pubkey(),
+                &authority.pubkey(),
+                self.get_rent_excemption(buffer_len),
+                data.len(),
+            )
+            .unwrap(),
+            &[&buffer],
+        )
+        .assert_success();
+
+        let mut offset = 0usize;
+        for chunk in data.chunks(900) {
+            println!("writing bytes {} to {}", offset, offset + chunk.len());
+            self.execute_as_transaction(
+                &[bpf_loader_upgradeable::write(
+                    &buffer.pubkey(),
+                    &authority.pubkey(),
+                    offset as u32,
+                    chunk.to_vec(),
+                )],
+                &[authority],
+            )
+            .assert_success();
+            offset += chunk.len();
+        }
+
+        buffer.pubkey()
+    }
+
+    /// Deploy a program with the upgradeable bpf loader. The program address is derived from the program and the upgrade authority,
+    /// so deploying the same program twice only deploys it once. The program can be upgraded to twice its current size.
+    fn deploy_upgradeable_program<P: AsRef<Path>>(
+        &mut self,
+        program_path: P,
+        upgrade_authority: &Keypair,
+    ) -> Pubkey {
+        let data = std::fs::read(&program_path).unwrap();
+        let mut hash = Sha256::default();
+        hash.update(&data);
+        hash.update(upgrade_authority.pubkey().as_ref());
+        let mut rng = StdRng::from_seed(hash.finalize()[..].try_into().unwrap());
+        let program = Keypair::generate(&mut rng);
+
+        if self.get_account(program.pubkey()).is_none() {
+            let buffer = self.create_program_buffer(&program_path, upgrade_authority);
+            let program_len = UpgradeableLoaderState::size_of_program();
+            self.execute_as_transaction(
+                &bpf_loader_upgradeable::deploy_with_max_program_len(
+                    &self.payer().pubkey(),
+                    &program.pubkey(),
+                    &buffer,
+                    &upgrade_authority.pubkey(),
+                    self.get_rent_excemption(program_len),
+                    data.len() * 2,
+                )
+                .unwrap(),
+                &[&program, upgrade_authority],
+            )
+            .assert_success();
+        }
+
+        program.pubkey()
+    }
+
+    /// Replace the code of an upgradeable program with the program at the given path.
+    fn upgrade_program<P: AsRef<Path>>(
+        &mut self,
+        program: Pubkey,
+        program_path: P,
+        upgrade_authority: &Keypair,
+    ) {
+        let buffer = self.create_program_buffer(program_path, upgrade_authority);
+        self.execute_as_transaction(
+            &[bpf_loader_upgradeable::upgrade(
+                &program,
+                &buffer,
+                &upgrade_authority.pubkey(),
+                &self.payer().pubkey(),
+            )],
+            &[upgrade_authority],
+        )
+        .assert_success();
+    }
+
     /// Gets and unpacks an account. None if the account does not exist.
     fn get_unpacked_account<T: Pack>(&self, pubkey: Pubkey) -> Option<T> {
         let acc = self.get_account(pubkey)?;
@@ -343,11 +582,58 @@ pub trait Environment {
     }
 }
 
+/// The keys that have to sign but are not among the signers, and the signers that don't have to sign.
+fn signature_mismatches(required: &[Pubkey], signers: &[&Keypair]) -> (Vec<Pubkey>, Vec<Pubkey>) {
+    let required_sigs = required.iter().copied().collect::<BTreeSet<_>>();
+    let provided_sigs = signers.iter().map(|x| x.pubkey()).collect::<BTreeSet<_>>();
+
+    (
+        required_sigs.difference(&provided_sigs).copied().collect(),
+        provided_sigs.difference(&required_sigs).copied().collect(),
+    )
+}
+
+fn print_signature_mismatches(required: &[Pubkey], signers: &[&Keypair]) {
+    let (missing, unnecessary) = signature_mismatches(required, signers);
+
+    for key in missing {
+        println!("missing signature from {}", key);
+    }
+
+    for key in unnecessary {
+        println!("unnecessary signature from {}", key);
+    }
+}
+
 /// An clean environment that executes transactions locally. Good for testing and debugging.
 /// This environment has the most important SPL programs: spl-token, spl-associated-token-account and spl-memo v1 and v3.
 pub struct LocalEnvironment {
-    bank: Bank,
+    bank: Arc<Bank>,
     faucet: Keypair,
+    compute_budget: Option<ComputeBudget>,
+    capture_dir: Option<PathBuf>,
+    invariants: Vec<Invariant>,
+    profiling: bool,
+    /// The program ids of the native programs added with [LocalEnvironmentBuilder::add_builtin_program].
+    builtin_programs: Vec<Pubkey>,
+    /// The directory of the accounts DB. Declared last, so it is removed after the bank is dropped.
+    tmpdir: Arc<TempDir>,
+}
+
+/// A saved state of a [LocalEnvironment], created by [LocalEnvironment::snapshot].
+/// Restoring it with [LocalEnvironment::restore] rolls back all accounts, the blockhash queue and the slot.
+#[derive(Clone)]
+pub struct Snapshot {
+    bank: Arc<Bank>,
+    /// The sysvars as updated by the slot advance of [LocalEnvironment::snapshot], which [LocalEnvironment::diff_since] compares against.
+    advanced_sysvars: Arc<HashMap<Pubkey, Account>>,
+}
+
+impl Snapshot {
+    /// The slot the environment was at when the snapshot was taken.
+    pub fn slot(&self) -> u64 {
+        self.bank.slot()
+    }
 }
 
 impl LocalEnvironment {
@@ -356,13 +642,169 @@ impl LocalEnvironment {
         LocalEnvironmentBuilder::new()
     }
 
+    /// Constructs a builder for a deterministic local environment. The payer, the validator and the genesis are derived from the seed,
+    /// so two runs of the same PoC produce the same blockhashes, transactions and signatures.
+    pub fn seeded_builder(seed: u64) -> LocalEnvironmentBuilder {
+        let mut builder = LocalEnvironmentBuilder::with_rng(&mut StdRng::seed_from_u64(seed));
+        builder.set_creation_time(SEEDED_CREATION_TIME);
+        builder
+    }
+
     /// Constructs a clean local environment.
     pub fn new() -> LocalEnvironment {
         Self::builder().build()
     }
 
     pub fn bank(&mut self) -> &mut Bank {
-        &mut self.bank
+        Arc::get_mut(&mut self.bank).expect("the working bank is never shared")
+    }
+
+    /// Check the invariant after every transaction executed from now on. Violations are listed by [Self::report].
+    pub fn add_invariant(&mut self, invariant: Invariant) {
+        self.invariants.push(invariant);
+    }
+
+    /// The changes of the attacker holdings and the results of all invariants, to judge whether an exploit worked.
+    pub fn report(&self, tracker: &BalanceTracker) -> Report {
+        Report {
+            changes: tracker.changes(self),
+            invariants: self
+                .invariants
+                .iter()
+                .map(|invariant| InvariantResult {
+                    name: invariant.name().to_string(),
+                    violations: invariant.violations().to_vec(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Record every transaction executed from now on, together with the state it was executed on, into the directory.
+    /// Each transaction is written to `<signature>.capture` and can be replayed with [TransactionCapture::replay].
+    pub fn capture_transactions<P: AsRef<Path>>(&mut self, dir: P) {
+        std::fs::create_dir_all(&dir).expect("couldn't create capture directory");
+        self.capture_dir = Some(dir.as_ref().to_path_buf());
+    }
+
+    /// Profile every transaction executed from now on. The outcomes contain the compute units, CPI depth,
+    /// syscalls and execution time of every invocation of a BPF program then, see [ExecutionOutcome::profile].
+    /// Profiled programs execute slower, as every instruction is traced.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profiling = enabled;
+    }
+
+    /// Save the current state of the environment. The returned handle can be restored any number of times.
+    ///
+    /// The current bank is frozen and all further transactions are executed on a child bank in the next slot.
+    pub fn snapshot(&mut self) -> Snapshot {
+        let parent = self.bank.clone();
+        self.bank = Arc::new(Bank::new_from_parent(
+            &parent,
+            parent.collector_id(),
+            parent.slot() + 1,
+        ));
+        let advanced_sysvars = self
+            .bank
+            .get_all_accounts_modified_since_parent()
+            .into_iter()
+            .filter(|(_, account)| account.owner() == &sysvar::ID)
+            .map(|(pubkey, account)| (pubkey, Account::from(account)))
+            .collect();
+        Snapshot {
+            bank: parent,
+            advanced_sysvars: Arc::new(advanced_sysvars),
+        }
+    }
+
+    /// Roll the environment back to the state it had when the snapshot was taken.
+    /// Snapshots taken after the given one are invalidated by this.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        // Purge the abandoned fork, so its slots can be reused by the restored bank.
+        self.purge_banks_since(snapshot);
+
+        self.bank = Arc::new(Bank::new_from_parent(
+            &snapshot.bank,
+            snapshot.bank.collector_id(),
+            snapshot.bank.slot() + 1,
+        ));
+    }
+
+    /// List every account whose lamports, owner, data or executable flag changed since the snapshot was taken, ordered by address.
+    /// The data is decoded for the accounts `solana-account-decoder` knows, like spl-token, stake, vote and sysvar accounts.
+    ///
+    /// Sysvars are compared to their state after the slot advance of [Self::snapshot], so the Clock and SlotHashes updates
+    /// of that advance are left out, but changes made by warping or [Self::set_sysvar] are listed.
+    pub fn diff_since(&self, snapshot: &Snapshot) -> Vec<AccountDiff> {
+        let pubkeys = self
+            .banks_since(snapshot)
+            .iter()
+            .flat_map(|bank| bank.get_all_accounts_modified_since_parent())
+            .map(|(pubkey, _)| pubkey)
+            .collect::<BTreeSet<_>>();
+
+        let get_before = |pubkey: &Pubkey| match snapshot.advanced_sysvars.get(pubkey) {
+            Some(account) => Some(account.clone()),
+            None => snapshot.bank.get_account(pubkey).map(Account::from),
+        };
+        let get_after = |pubkey: &Pubkey| self.get_account(*pubkey);
+        pubkeys
+            .into_iter()
+            .filter_map(|pubkey| {
+                let before = get_before(&pubkey);
+                let after = get_after(&pubkey);
+                let changed = match (&before, &after) {
+                    (Some(before), Some(after)) => {
+                        before.lamports != after.lamports
+                            || before.owner != after.owner
+                            || before.data != after.data
+                            || before.executable != after.executable
+                    }
+                    (None, None) => false,
+                    _ => true,
+                };
+                changed.then(|| AccountDiff {
+                    pubkey,
+                    parsed_before: before
+                        .as_ref()
+                        .and_then(|account| diff::parse_account(&pubkey, account, get_before)),
+                    parsed_after: after
+                        .as_ref()
+                        .and_then(|account| diff::parse_account(&pubkey, account, get_after)),
+                    before,
+                    after,
+                })
+            })
+            .collect()
+    }
+
+    /// Removes the working bank and all its parents that were created after the snapshot from the accounts DB and the status cache.
+    fn purge_banks_since(&self, snapshot: &Snapshot) {
+        let abandoned = self
+            .banks_since(snapshot)
+            .iter()
+            .map(|bank| (bank.slot(), bank.bank_id()))
+            .collect::<Vec<_>>();
+        self.bank.remove_unrooted_slots(&abandoned);
+        for (slot, _) in &abandoned {
+            self.bank.clear_slot_signatures(*slot);
+        }
+    }
+
+    /// The working bank and all its parents that were created after the snapshot, newest first.
+    fn banks_since(&self, snapshot: &Snapshot) -> Vec<Arc<Bank>> {
+        let banks = std::iter::once(self.bank.clone())
+            .chain(self.bank.parents())
+            .collect::<Vec<_>>();
+        match banks
+            .iter()
+            .position(|bank| bank.bank_id() == snapshot.bank.bank_id())
+        {
+            Some(position) => banks[..position].to_vec(),
+            None => panic!(
+                "snapshot of slot {} was invalidated by restoring an earlier snapshot",
+                snapshot.slot()
+            ),
+        }
     }
 
     /// Advance the bank to the next blockhash.
@@ -376,23 +818,200 @@ impl LocalEnvironment {
         for _ in 0..parent_distance {
             let last_blockhash = self.bank.last_blockhash();
             while self.bank.last_blockhash() == last_blockhash {
-                self.bank.register_tick(&Hash::new_unique())
+                // Derived from the bank instead of random, so the blockhashes only depend on the genesis
+                let tick = hashv(&[
+                    self.bank.last_blockhash().as_ref(),
+                    &self.bank.tick_height().to_le_bytes(),
+                ]);
+                self.bank.register_tick(&tick)
             }
         }
 
         self.get_latest_blockhash()
     }
-}
 
-impl Environment for LocalEnvironment {
-    fn payer(&self) -> Keypair {
-        clone_keypair(&self.faucet)
+    /// Jump ahead to the given slot. Sysvars like the clock and the slot hashes are updated as if all slots in between were skipped.
+    /// The unix timestamp does not move along with the slot, use [LocalEnvironment::set_unix_timestamp] for that.
+    pub fn warp_to_slot(&mut self, slot: Slot) {
+        let current = self.bank.slot();
+        if slot <= current {
+            panic!("can not warp back from slot {} to slot {}", current, slot);
+        }
+
+        // Warp to the slot before the target, as warped banks are frozen and can't execute transactions
+        let parent = if slot - 1 == current {
+            self.bank.clone()
+        } else {
+            Arc::new(Bank::warp_from_parent(
+                &self.bank,
+                self.bank.collector_id(),
+                slot - 1,
+            ))
+        };
+        self.bank = Arc::new(Bank::new_from_parent(&parent, parent.collector_id(), slot));
+    }
+
+    /// Jump ahead to the first slot of the given epoch.
+    pub fn warp_to_epoch(&mut self, epoch: Epoch) {
+        let slot = self.bank.epoch_schedule().get_first_slot_in_epoch(epoch);
+        self.warp_to_slot(slot);
     }
 
-    fn execute_transaction(
+    /// The current clock sysvar.
+    pub fn clock(&self) -> Clock {
+        self.bank.clock()
+    }
+
+    /// Overwrite the unix timestamp of the clock sysvar.
+    pub fn set_unix_timestamp(&mut self, unix_timestamp: UnixTimestamp) {
+        let clock = Clock {
+            unix_timestamp,
+            ..self.clock()
+        };
+        self.set_sysvar(&clock);
+    }
+
+    /// Overwrite a sysvar. Programs see the new value starting with the next transaction.
+    pub fn set_sysvar<T: Sysvar + SysvarId>(&mut self, sysvar: &T) {
+        self.bank.set_sysvar_for_tests(sysvar);
+    }
+
+    /// Overwrite an account, bypassing all runtime checks. The change is visible to the next transaction.
+    pub fn set_account(&mut self, pubkey: Pubkey, account: Account) {
+        let old_lamports = self.get_account(pubkey).map_or(0, |acc| acc.lamports);
+        let is_program = account.executable || account.owner == bpf_loader_upgradeable::ID;
+        let lamports = account.lamports;
+        self.bank
+            .store_account(&pubkey, &AccountSharedData::from(account));
+
+        // The created or destroyed lamports have to be accounted for, otherwise the bank fails to freeze
+        if lamports != old_lamports {
+            self.bank.set_capitalization();
+        }
+        // Programs are cached after their first invocation
+        if is_program {
+            self.bank.clear_executors();
+        }
+    }
+
+    /// Overwrite the lamports of an account. Creates a system account if it does not exist yet.
+    pub fn set_lamports(&mut self, pubkey: Pubkey, lamports: u64) {
+        let mut account = self.get_account(pubkey).unwrap_or_default();
+        account.lamports = lamports;
+        self.set_account(pubkey, account);
+    }
+
+    /// Overwrite the data of an existing account. The lamports are not adjusted to the new size.
+    pub fn set_account_data(&mut self, pubkey: Pubkey, data: &[u8]) {
+        let mut account = self.get_account(pubkey).expect("account does not exist");
+        account.data = data.to_vec();
+        self.set_account(pubkey, account);
+    }
+
+    /// Overwrite the balance of a token account. The supply of the mint changes accordingly.
+    /// Panics if the supply of the mint is smaller than the old balance or would overflow.
+    pub fn set_token_balance(&mut self, token_account: Pubkey, amount: u64) {
+        let mut account = self
+            .get_unpacked_account::<spl_token::state::Account>(token_account)
+            .expect("token account does not exist");
+        let mut mint = self
+            .get_unpacked_account::<spl_token::state::Mint>(account.mint)
+            .expect("mint does not exist");
+        mint.supply = mint
+            .supply
+            .checked_sub(account.amount)
+            .expect("mint supply is smaller than the token account balance")
+            .checked_add(amount)
+            .expect("mint supply overflows");
+        account.amount = amount;
+        self.set_packed(token_account, account);
+        self.set_packed(account.mint, mint);
+    }
+
+    fn set_packed<P: Pack>(&mut self, pubkey: Pubkey, data: P) {
+        let mut packed = vec![0u8; P::LEN];
+        data.pack_into_slice(&mut packed[..]);
+        self.set_account_data(pubkey, &packed);
+    }
+
+    /// Executes the transaction like [Environment::execute_versioned_transaction] and returns an instruction-level trace
+    /// of every invoked BPF program, with the registers before every instruction, the disassembly and the function names from the ELF.
+    /// Programs execute a lot slower while traced.
+    pub fn execute_transaction_traced<T: Into<VersionedTransaction>>(
         &mut self,
-        tx: Transaction,
-    ) -> EncodedConfirmedTransactionWithStatusMeta {
+        tx: T,
+    ) -> (ExecutionOutcome, ExecutionTrace) {
+        let (outcome, trace) = self.execute_recorded(tx.into(), true);
+        (outcome, trace.unwrap_or_default())
+    }
+
+    /// Assembles the instructions into a transaction like [Environment::tx_with_instructions], but leaves out the signers it doesn't need
+    /// instead of printing the mismatches. None if a signature is missing.
+    pub(crate) fn sign_instructions(
+        &self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Option<Transaction> {
+        let payer = self.payer();
+        let mut signer_vec = vec![&payer];
+        signer_vec.extend_from_slice(signers);
+
+        let message = Message::new(instructions, Some(&payer.pubkey()));
+        let num_sigs: usize = message.header.num_required_signatures.into();
+        let (missing, unnecessary) =
+            signature_mismatches(&message.account_keys[..num_sigs], &signer_vec);
+        if !missing.is_empty() {
+            return None;
+        }
+        signer_vec.retain(|signer| !unnecessary.contains(&signer.pubkey()));
+
+        Some(Transaction::new(
+            &signer_vec,
+            message,
+            self.get_latest_blockhash(),
+        ))
+    }
+
+    /// Executes the transaction with captures, profiling and tracing, and checks the invariants afterwards.
+    fn execute_recorded(
+        &mut self,
+        tx: VersionedTransaction,
+        traced: bool,
+    ) -> (ExecutionOutcome, Option<ExecutionTrace>) {
+        let capture = self
+            .capture_dir
+            .is_some()
+            .then(|| self.begin_capture(&tx))
+            .flatten();
+        if self.profiling {
+            profile::begin();
+        }
+        if traced {
+            trace::begin();
+        }
+        let mut timings = ExecuteTimings::default();
+        let mut outcome = self.execute_in_bank(tx, &mut timings);
+        if self.profiling {
+            outcome.profile = Some(profile::finish(&timings));
+        }
+        let trace = traced.then(trace::finish);
+        if self.profiling || traced {
+            // The executors that recorded the programs must not execute them later on
+            self.bank.clear_executors();
+        }
+        if let Some(capture) = capture {
+            self.finish_capture(capture, &outcome);
+        }
+        self.check_invariants(&outcome);
+        (outcome, trace)
+    }
+
+    /// Executes the transaction on the working bank.
+    fn execute_in_bank(
+        &mut self,
+        tx: VersionedTransaction,
+        timings: &mut ExecuteTimings,
+    ) -> ExecutionOutcome {
         let len = bincode::serialize(&tx).unwrap().len();
         if len > packet::PACKET_DATA_SIZE {
             panic!(
@@ -404,12 +1023,14 @@ impl Environment for LocalEnvironment {
         }
         let txs = vec![tx];
 
-        let batch = self.bank.prepare_batch_for_tests(txs.clone());
+        let batch = self
+            .bank
+            .prepare_entry_batch(txs.clone())
+            .expect("Failed to sanitize transaction");
         let mut mint_decimals = HashMap::new();
         let tx_pre_token_balances =
             token_balances::collect_token_balances(&self.bank, &batch, &mut mint_decimals);
         let slot = self.bank.slot();
-        let mut timings = Default::default();
         let (
             TransactionResults {
                 execution_results, ..
@@ -426,13 +1047,14 @@ impl Environment for LocalEnvironment {
             true,
             true,
             true,
-            &mut timings,
+            timings,
         );
 
         let tx_post_token_balances =
             token_balances::collect_token_balances(&self.bank, &batch, &mut mint_decimals);
         izip!(
             txs.iter(),
+            batch.sanitized_transactions().iter(),
             execution_results.into_iter(),
             pre_balances.into_iter(),
             post_balances.into_iter(),
@@ -442,26 +1064,28 @@ impl Environment for LocalEnvironment {
         .map(
             |(
                 tx,
+                sanitized_tx,
                 execution_result,
                 pre_balances,
                 post_balances,
                 pre_token_balances,
                 post_token_balances,
             ): (
-                &Transaction,
+                &VersionedTransaction,
+                &SanitizedTransaction,
                 TransactionExecutionResult,
                 Vec<u64>,
                 Vec<u64>,
                 Vec<TransactionTokenBalance>,
                 Vec<TransactionTokenBalance>,
             )| {
-                let fee = self.bank.get_fee_for_message(&SanitizedMessage::try_from(tx.message().clone()).expect("Failed to sanitize transaction"))
+                let fee = self.bank.get_fee_for_message(sanitized_tx.message())
                     .expect("Fee calculation must succeed");
 
-                let (status, inner_instructions, log_messages) = match execution_result {
-                    TransactionExecutionResult::Executed { details: TransactionExecutionDetails { status, inner_instructions, log_messages, .. }, .. } =>
-                        (status, inner_instructions, log_messages),
-                    TransactionExecutionResult::NotExecuted(err) => (Err(err), None, None)
+                let (status, inner_instructions, log_messages, return_data, executed_units) = match execution_result {
+                    TransactionExecutionResult::Executed { details: TransactionExecutionDetails { status, inner_instructions, log_messages, return_data, executed_units, .. }, .. } =>
+                        (status, inner_instructions, log_messages, return_data, Some(executed_units)),
+                    TransactionExecutionResult::NotExecuted(err) => (Err(err), None, None, None, None)
                 };
 
                 let inner_instructions = inner_instructions.map(|inner_instructions| {
@@ -486,17 +1110,14 @@ impl Environment for LocalEnvironment {
                     inner_instructions,
                     log_messages,
                     rewards: None,
-                    loaded_addresses: LoadedAddresses {
-                        writable: vec![], // TODO
-                        readonly: vec![], // TODO
-                    },
-                    return_data: None
+                    loaded_addresses: sanitized_tx.get_loaded_addresses(),
+                    return_data,
                 };
 
-                ConfirmedTransactionWithStatusMeta {
+                let mut outcome = ConfirmedTransactionWithStatusMeta {
                     slot,
                     tx_with_meta: TransactionWithStatusMeta::Complete(VersionedTransactionWithStatusMeta {
-                        transaction: VersionedTransaction::from(tx.clone()),
+                        transaction: tx.clone(),
                         meta: tx_status_meta,
                     }),
                     block_time: Some(
@@ -508,12 +1129,69 @@ impl Environment for LocalEnvironment {
                             .unwrap(),
                     ),
                 }
-                .encode(UiTransactionEncoding::Binary, None)
-                .expect("Failed to encode transaction")
+                .encode(UiTransactionEncoding::Binary, Some(0))
+                .map(ExecutionOutcome::from_encoded)
+                .expect("Failed to encode transaction");
+                // The bank knows the exact amount, there is no need to rely on the logs
+                outcome.compute_units_consumed = executed_units.or(outcome.compute_units_consumed);
+                outcome
             },
         )
         .next().expect("transaction could not be executed. Enable debug logging to get more information on why")
     }
+}
+
+impl Environment for LocalEnvironment {
+    fn payer(&self) -> Keypair {
+        clone_keypair(&self.faucet)
+    }
+
+    fn execute_versioned_transaction(&mut self, tx: VersionedTransaction) -> ExecutionOutcome {
+        self.execute_recorded(tx, false).0
+    }
+
+    fn try_execute_versioned_transaction(
+        &mut self,
+        tx: VersionedTransaction,
+    ) -> Result<ExecutionOutcome, TransactionFailure> {
+        self.execute_versioned_transaction(tx).into_result()
+    }
+
+    fn simulate_transaction(&self, tx: Transaction) -> SimulationOutcome {
+        let tx = match SanitizedTransaction::try_create(
+            VersionedTransaction::from(tx),
+            MessageHash::Compute,
+            None,
+            &*self.bank,
+            self.bank
+                .feature_set
+                .is_active(&feature_set::require_static_program_ids_in_transaction::ID),
+        ) {
+            Ok(tx) => tx,
+            Err(err) => {
+                return SimulationOutcome {
+                    status: Err(err),
+                    logs: vec![],
+                    accounts: vec![],
+                    compute_units_consumed: None,
+                    return_data: None,
+                }
+            }
+        };
+        // The working bank is never frozen, so the frozen check of `simulate_transaction` would always fail
+        let result = self.bank.simulate_transaction_unchecked(tx);
+        SimulationOutcome {
+            status: result.result,
+            logs: result.logs,
+            accounts: result
+                .post_simulation_accounts
+                .into_iter()
+                .map(|(pubkey, account)| (pubkey, account.into()))
+                .collect(),
+            compute_units_consumed: Some(result.units_consumed),
+            return_data: result.return_data,
+        }
+    }
 
     fn get_latest_blockhash(&self) -> Hash {
         self.bank.last_blockhash()
@@ -531,21 +1209,59 @@ impl Environment for LocalEnvironment {
 pub struct LocalEnvironmentBuilder {
     config: GenesisConfig,
     faucet: Keypair,
+    compute_budget: Option<ComputeBudget>,
+    /// The accounts added through the builder, as opposed to the accounts every environment starts with.
+    added_accounts: HashSet<Pubkey>,
+    builtins: Vec<Builtin>,
 }
 
 impl LocalEnvironmentBuilder {
     fn new() -> Self {
-        let faucet = random_keypair();
+        Self::with_rng(&mut OsRng)
+    }
+
+    /// Creates the payer and the validator with the given rng.
+    fn with_rng<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        let faucet = Keypair::generate(rng);
+
+        // A staked validator is required to distribute the collected rent whenever a slot ends.
+        // Its accounts must not collide with the ones of the user, so `Pubkey::new_unique` can't be used.
+        let validator = Keypair::generate(rng).pubkey();
+        let vote_account = Keypair::generate(rng).pubkey();
+        let validator_stake = sol_to_lamports(1_000_000.0);
+        let vote = vote_state::create_account(&vote_account, &validator, 0, validator_stake);
+        let stake = stake_state::create_account(
+            &validator,
+            &vote_account,
+            &vote,
+            &Rent::default(),
+            validator_stake,
+        );
+
         let mut config = GenesisConfig::new(
-            &[(
-                faucet.pubkey(),
-                AccountSharedData::new(1u64 << 48, 0, &system_program::id()),
-            )],
+            &[
+                (
+                    faucet.pubkey(),
+                    AccountSharedData::new(1u64 << 48, 0, &system_program::id()),
+                ),
+                (
+                    validator,
+                    AccountSharedData::new(sol_to_lamports(1.0), 0, &system_program::id()),
+                ),
+                (vote_account, vote),
+                (Keypair::generate(rng).pubkey(), stake),
+            ],
             &[],
         );
         genesis_utils::activate_all_features(&mut config);
 
-        let mut builder = LocalEnvironmentBuilder { faucet, config };
+        let mut builder = LocalEnvironmentBuilder {
+            faucet,
+            config,
+            compute_budget: None,
+            added_accounts: HashSet::new(),
+            builtins: vec![],
+        };
         builder.add_account_with_data(
             spl_associated_token_account::ID,
             bpf_loader::ID,
@@ -563,27 +1279,203 @@ impl LocalEnvironmentBuilder {
         builder.add_account_with_data(spl_memo::ID, bpf_loader::ID, programs::SPL_MEMO3, true);
         builder.add_account_with_data(spl_token::ID, bpf_loader::ID, programs::SPL_TOKEN, true);
         builder.add_account_with_lamports(rent::ID, sysvar::ID, 1);
+        // Slot hashes are only recorded once the first slot is over, but address lookups already need them in the genesis slot
+        let lamports = builder.config.rent.minimum_balance(SlotHashes::size_of());
+        builder.add_account(
+            slot_hashes::ID,
+            account::create_account_with_fields(&SlotHashes::default(), (lamports, 0)),
+        );
+        builder.added_accounts.clear();
         builder
     }
 
+    /// Pays for the transactions of the environment with the given keypair instead of a random one, e.g. [keypair]`(255)`.
+    pub fn set_payer(&mut self, payer: Keypair) -> &mut Self {
+        let account = self
+            .config
+            .accounts
+            .remove(&self.faucet.pubkey())
+            .expect("payer account does not exist");
+        self.config.accounts.insert(payer.pubkey(), account);
+        self.faucet = payer;
+        self
+    }
+
     /// Sets the creation time of the network
     pub fn set_creation_time(&mut self, unix_timestamp: UnixTimestamp) -> &mut Self {
         self.config.creation_time = unix_timestamp as UnixTimestamp;
         self
     }
 
+    /// Deactivates a feature. All features are active by default.
+    pub fn deactivate_feature(&mut self, feature_id: Pubkey) -> &mut Self {
+        self.config.accounts.remove(&feature_id);
+        self
+    }
+
+    /// Activates exactly the features that are active in the given feature set.
+    pub fn with_feature_set(&mut self, feature_set: &FeatureSet) -> &mut Self {
+        for feature_id in FeatureSet::default().inactive {
+            self.deactivate_feature(feature_id);
+        }
+        for feature_id in feature_set.active.keys() {
+            genesis_utils::activate_feature(&mut self.config, *feature_id);
+        }
+        self
+    }
+
+    /// Sets the compute units every transaction can consume.
+    /// Compute budget instructions in transactions are ignored then.
+    pub fn set_compute_max_units(&mut self, units: u64) -> &mut Self {
+        self.compute_budget = Some(ComputeBudget::new(units));
+        self
+    }
+
     /// Adds the account into the environment.
     pub fn add_account(&mut self, pubkey: Pubkey, account: Account) -> &mut Self {
         self.config.add_account(pubkey, account.into());
+        self.added_accounts.insert(pubkey);
+        self
+    }
+
+    /// Writes all accounts added to the builder into the directory, one `<pubkey>.json` file per account.
+    /// The files have the format of `solana account --output json`, so they can also be created with the solana cli.
+    pub fn export_accounts<P: AsRef<Path>>(&mut self, path: P) -> &mut Self {
+        let path = path.as_ref();
+        std::fs::create_dir_all(path).expect("couldn't create account directory");
+        let mut pubkeys = self.added_accounts.clone();
+        for pubkey in &self.added_accounts {
+            if let Some(programdata_address) = self.programdata_address(pubkey) {
+                pubkeys.insert(programdata_address);
+            }
+        }
+        for pubkey in pubkeys {
+            let account = self
+                .config
+                .accounts
+                .get(&pubkey)
+                .unwrap_or_else(|| panic!("account {} does not exist", pubkey));
+            let keyed_account = RpcKeyedAccount {
+                pubkey: pubkey.to_string(),
+                account: UiAccount::encode(
+                    &pubkey,
+                    &AccountSharedData::from(account.clone()),
+                    UiAccountEncoding::Base64,
+                    None,
+                    None,
+                ),
+            };
+            let file = std::fs::File::create(path.join(format!("{}.json", pubkey)))
+                .expect("couldn't create account file");
+            serde_json::to_writer_pretty(file, &keyed_account)
+                .expect("couldn't write account file");
+        }
         self
     }
 
+    /// Adds all accounts from the `.json` files in the directory, as written by [Self::export_accounts] or `solana account --output json`.
+    /// Panics if the program data account of an upgradeable program is neither in the directory nor already added.
+    pub fn add_accounts_from_dir<P: AsRef<Path>>(&mut self, path: P) -> &mut Self {
+        let mut pubkeys = vec![];
+        for entry in std::fs::read_dir(path).expect("couldn't read account directory") {
+            let path = entry.unwrap().path();
+            if path.extension() != Some("json".as_ref()) {
+                continue;
+            }
+            let file = std::fs::File::open(&path).expect("couldn't open account file");
+            let keyed_account: RpcKeyedAccount = serde_json::from_reader(file)
+                .unwrap_or_else(|err| panic!("couldn't parse {}: {}", path.display(), err));
+            let pubkey: Pubkey = keyed_account.pubkey.parse().expect("invalid pubkey");
+            let account: Account = keyed_account
+                .account
+                .decode()
+                .unwrap_or_else(|| panic!("couldn't decode account data of {}", pubkey));
+            self.add_account(pubkey, account);
+            pubkeys.push(pubkey);
+        }
+        for pubkey in pubkeys {
+            if let Some(programdata_address) = self.programdata_address(&pubkey) {
+                if !self.config.accounts.contains_key(&programdata_address) {
+                    panic!(
+                        "program data account {} of program {} is missing",
+                        programdata_address, pubkey
+                    );
+                }
+            }
+        }
+        self
+    }
+
+    /// The program data address of an account, if it is a program of the upgradeable bpf loader.
+    fn programdata_address(&self, pubkey: &Pubkey) -> Option<Pubkey> {
+        let account = self.config.accounts.get(pubkey)?;
+        if account.owner != bpf_loader_upgradeable::ID {
+            return None;
+        }
+        match bincode::deserialize(&account.data) {
+            Ok(UpgradeableLoaderState::Program {
+                programdata_address,
+            }) => Some(programdata_address),
+            _ => None,
+        }
+    }
+
     /// Reads the program from the path and add it at the address into the environment.
     pub fn add_program<P: AsRef<Path>>(&mut self, pubkey: Pubkey, path: P) -> &mut Self {
         self.add_account_with_data(pubkey, bpf_loader::ID, &std::fs::read(path).unwrap(), true);
         self
     }
 
+    /// Adds a native program, which runs as a builtin instead of in the BPF VM.
+    /// Use the [processor] macro to add the `process_instruction` of a program crate,
+    /// so it can be debugged with breakpoints and `println!`:
+    /// ```ignore
+    /// builder.add_builtin_program("level0", program_id, processor!(level0::processor::process_instruction));
+    /// ```
+    pub fn add_builtin_program(
+        &mut self,
+        name: &str,
+        pubkey: Pubkey,
+        process_instruction: ProcessInstructionWithContext,
+    ) -> &mut Self {
+        builtin::install_syscall_stubs();
+        self.builtins
+            .push(Builtin::new(name, pubkey, process_instruction));
+        self
+    }
+
+    /// Adds a program owned by the upgradeable bpf loader, together with its program data account.
+    /// Without an upgrade authority, the program is immutable.
+    pub fn add_upgradeable_program<P: AsRef<Path>>(
+        &mut self,
+        pubkey: Pubkey,
+        path: P,
+        upgrade_authority: Option<Pubkey>,
+    ) -> &mut Self {
+        let (programdata_address, _) =
+            Pubkey::find_program_address(&[pubkey.as_ref()], &bpf_loader_upgradeable::ID);
+        let program = bincode::serialize(&UpgradeableLoaderState::Program {
+            programdata_address,
+        })
+        .unwrap();
+        let mut programdata = bincode::serialize(&UpgradeableLoaderState::ProgramData {
+            slot: 0,
+            upgrade_authority_address: upgrade_authority,
+        })
+        .unwrap();
+        programdata.resize(UpgradeableLoaderState::size_of_programdata_metadata(), 0);
+        programdata.extend_from_slice(&std::fs::read(path).unwrap());
+
+        self.add_account_with_data(pubkey, bpf_loader_upgradeable::ID, &program, true);
+        self.add_account_with_data(
+            programdata_address,
+            bpf_loader_upgradeable::ID,
+            &programdata,
+            false,
+        );
+        self
+    }
+
     // Adds a rent-excempt account into the environment.
     pub fn add_account_with_data(
         &mut self,
@@ -707,6 +1599,65 @@ impl LocalEnvironmentBuilder {
         )
     }
 
+    /// Adds an address lookup table containing the given addresses. All addresses can be looked up right away.
+    pub fn add_address_lookup_table(
+        &mut self,
+        pubkey: Pubkey,
+        authority: Option<Pubkey>,
+        addresses: &[Pubkey],
+    ) -> &mut Self {
+        let meta = LookupTableMeta {
+            authority,
+            ..LookupTableMeta::default()
+        };
+        self.set_address_lookup_table(pubkey, meta, addresses.to_vec())
+    }
+
+    /// Appends addresses to an address lookup table previously added to this builder.
+    pub fn extend_address_lookup_table(
+        &mut self,
+        pubkey: Pubkey,
+        addresses: &[Pubkey],
+    ) -> &mut Self {
+        let account = self
+            .config
+            .accounts
+            .get(&pubkey)
+            .expect("address lookup table does not exist");
+        let table = AddressLookupTable::deserialize(&account.data)
+            .expect("account is no address lookup table");
+        let mut all_addresses = table.addresses.to_vec();
+        all_addresses.extend_from_slice(addresses);
+        self.set_address_lookup_table(pubkey, table.meta, all_addresses)
+    }
+
+    fn set_address_lookup_table(
+        &mut self,
+        pubkey: Pubkey,
+        mut meta: LookupTableMeta,
+        addresses: Vec<Pubkey>,
+    ) -> &mut Self {
+        // The environment starts at slot 0. Addresses extended in the current slot are not active yet,
+        // so all of them are marked as being there before the last extension.
+        meta.last_extended_slot = 0;
+        meta.last_extended_slot_start_index = addresses
+            .len()
+            .try_into()
+            .expect("too many addresses for a lookup table in the genesis slot");
+        let data = AddressLookupTable {
+            meta,
+            addresses: addresses.into(),
+        }
+        .serialize_for_tests()
+        .expect("Failed to serialize address lookup table");
+        self.add_account_with_data(
+            pubkey,
+            solana_address_lookup_table_program::ID,
+            &data,
+            false,
+        )
+    }
+
     /// Clone an account from a cluster using the given rpc client. Use [clone_upgradable_program_from_cluster] if you want to clone a upgradable program, as this requires multiple accounts.
     pub fn clone_account_from_cluster(&mut self, pubkey: Pubkey, client: &RpcClient) -> &mut Self {
         println!("Loading account {} from cluster", pubkey);
@@ -737,6 +1688,64 @@ impl LocalEnvironmentBuilder {
         self
     }
 
+    /// Clones every account a historical transaction touched from the cluster, including the accounts loaded from address lookup tables and the lookup tables themselves.
+    /// Upgradeable programs are cloned together with their program data. Native programs and sysvars are skipped, as well as accounts that do not exist anymore.
+    /// Note that the accounts are cloned in their current state, not in the state they had when the transaction was executed.
+    pub fn clone_transaction_accounts_from_cluster(
+        &mut self,
+        signature: Signature,
+        client: &RpcClient,
+    ) -> &mut Self {
+        println!("Loading accounts of transaction {} from cluster", signature);
+        let transaction = client
+            .get_transaction_with_config(
+                &signature,
+                RpcTransactionConfig {
+                    encoding: Some(UiTransactionEncoding::Binary),
+                    commitment: Some(CommitmentConfig::confirmed()),
+                    max_supported_transaction_version: Some(0),
+                },
+            )
+            .expect("couldn't retrieve transaction");
+        let outcome = ExecutionOutcome::from_encoded(transaction);
+
+        let mut pubkeys: Vec<Pubkey> = outcome
+            .lamport_changes
+            .iter()
+            .map(|change| change.pubkey)
+            .collect();
+        if let Some(lookups) = outcome.transaction.message.address_table_lookups() {
+            pubkeys.extend(lookups.iter().map(|lookup| lookup.account_key));
+        }
+        let accounts = pubkeys
+            .chunks(100)
+            .flat_map(|chunk| {
+                client
+                    .get_multiple_accounts(chunk)
+                    .expect("couldn't retrieve accounts")
+            })
+            .collect::<Vec<_>>();
+
+        for (&pubkey, account) in pubkeys.iter().zip(accounts) {
+            let mut account = match account {
+                Some(account) => account,
+                None => continue,
+            };
+            if account.owner == native_loader::ID || account.owner == sysvar::ID {
+                continue;
+            }
+            println!("Loading account {} from cluster", pubkey);
+            account.rent_epoch = 0;
+            self.add_account(pubkey, account);
+            if let Some(programdata_address) = self.programdata_address(&pubkey) {
+                if !pubkeys.contains(&programdata_address) {
+                    self.clone_account_from_cluster(programdata_address, client);
+                }
+            }
+        }
+        self
+    }
+
     /// Clones all accounts required to execute the given executable program from the cluster, using the given rpc client.
     pub fn clone_upgradable_program_from_cluster(
         &mut self,
@@ -762,11 +1771,14 @@ impl LocalEnvironmentBuilder {
 
     /// Finalizes the environment.
     pub fn build(&mut self) -> LocalEnvironment {
-        let tmpdir = Path::new("/tmp/");
+        let tmpdir = tempfile::Builder::new()
+            .prefix("poc-framework-")
+            .tempdir()
+            .expect("couldn't create the directory of the accounts DB");
 
-        let bank = Bank::new_with_paths(
+        let mut bank = Bank::new_with_paths(
             &self.config,
-            vec![tmpdir.to_path_buf()],
+            vec![tmpdir.path().to_path_buf()],
             None,
             Some(&Builtins {
                 genesis_builtins: [
@@ -775,7 +1787,9 @@ impl LocalEnvironmentBuilder {
                     solana_bpf_loader_deprecated_program!(),
                 ]
                 .iter()
-                .map(|p| Builtin::new(&p.0, p.1, p.2))
+                // All loaders share one entrypoint, which is wrapped to profile the programs
+                .map(|p| Builtin::new(&p.0, p.1, profile::process_instruction))
+                .chain(self.builtins.iter().cloned())
                 .collect(),
                 feature_transitions: vec![],
             }),
@@ -790,9 +1804,17 @@ impl LocalEnvironmentBuilder {
             None,
         );
 
+        bank.set_compute_budget(self.compute_budget);
+
         let env = LocalEnvironment {
-            bank,
+            bank: Arc::new(bank),
             faucet: clone_keypair(&self.faucet),
+            compute_budget: self.compute_budget,
+            capture_dir: None,
+            invariants: vec![],
+            profiling: false,
+            builtin_programs: self.builtins.iter().map(|builtin| builtin.id).collect(),
+            tmpdir: Arc::new(tmpdir),
         };
         env.advance_blockhash();
 
@@ -819,6 +1841,21 @@ impl RemoteEnvironment {
         env
     }
 
+    /// Fetch a confirmed transaction and decode it. None if it could not be fetched.
+    fn get_outcome(&self, sig: &Signature) -> Option<ExecutionOutcome> {
+        self.client
+            .get_transaction_with_config(
+                sig,
+                RpcTransactionConfig {
+                    encoding: Some(UiTransactionEncoding::Binary),
+                    commitment: Some(CommitmentConfig::confirmed()),
+                    max_supported_transaction_version: Some(0),
+                },
+            )
+            .ok()
+            .map(ExecutionOutcome::from_encoded)
+    }
+
     /// Airdrop lamports up to the given balance to the account.
     pub fn airdrop(&self, account: Pubkey, lamports: u64) {
         if self.client.get_balance(&account).expect("get balance") < lamports {
@@ -840,24 +1877,133 @@ impl Environment for RemoteEnvironment {
         clone_keypair(&self.payer)
     }
 
-    fn execute_transaction(
+    fn execute_versioned_transaction(&mut self, tx: VersionedTransaction) -> ExecutionOutcome {
+        match self.try_execute_versioned_transaction(tx) {
+            Ok(outcome) => outcome,
+            Err(TransactionFailure {
+                outcome: Some(outcome),
+                ..
+            }) => *outcome,
+            Err(failure) => panic!("{}", failure),
+        }
+    }
+
+    fn try_execute_versioned_transaction(
         &mut self,
-        tx: Transaction,
-    ) -> EncodedConfirmedTransactionWithStatusMeta {
-        let sig = match self.client.send_and_confirm_transaction(&tx) {
-            Err(e) => panic!("{:#?}", e),
-            Ok(sig) => sig,
+        tx: VersionedTransaction,
+    ) -> Result<ExecutionOutcome, TransactionFailure> {
+        let result = match tx.clone().into_legacy_transaction() {
+            Some(tx) => self.client.send_and_confirm_transaction(&tx),
+            // RpcClient can only send legacy transactions, so v0 transactions are sent with a raw request
+            None => {
+                let config = RpcSendTransactionConfig {
+                    encoding: Some(UiTransactionEncoding::Base64),
+                    preflight_commitment: Some(self.client.commitment().commitment),
+                    ..RpcSendTransactionConfig::default()
+                };
+                let serialized = base64::encode(bincode::serialize(&tx).unwrap());
+                match self
+                    .client
+                    .send::<String>(RpcRequest::SendTransaction, json!([serialized, config]))
+                {
+                    Ok(_) => self
+                        .client
+                        .confirm_transaction_with_spinner(
+                            &tx.signatures[0],
+                            tx.message.recent_blockhash(),
+                            CommitmentConfig::confirmed(),
+                        )
+                        .map(|()| tx.signatures[0]),
+                    Err(err) => Err(err),
+                }
+            }
         };
-        self.client
-            .get_transaction_with_config(
-                &sig,
-                RpcTransactionConfig {
-                    encoding: Some(UiTransactionEncoding::Binary),
-                    commitment: Some(CommitmentConfig::confirmed()),
-                    ..RpcTransactionConfig::default()
+        let err = match result {
+            Ok(sig) => {
+                return Ok(self
+                    .get_outcome(&sig)
+                    .expect("Failed to fetch confirmed transaction"))
+            }
+            Err(err) => err,
+        };
+        match err.kind() {
+            ClientErrorKind::RpcError(RpcError::RpcResponseError {
+                data:
+                    RpcResponseErrorData::SendTransactionPreflightFailure(
+                        RpcSimulateTransactionResult {
+                            err: Some(error),
+                            logs,
+                            units_consumed,
+                            ..
+                        },
+                    ),
+                ..
+            }) => Err(TransactionFailure {
+                error: error.clone(),
+                logs: logs.clone().unwrap_or_default(),
+                compute_units_consumed: *units_consumed,
+                outcome: None,
+            }),
+            // The transaction landed, but failed during execution
+            ClientErrorKind::TransactionError(error) => match self.get_outcome(&tx.signatures[0]) {
+                Some(outcome) => outcome.into_result(),
+                None => Err(TransactionFailure {
+                    error: error.clone(),
+                    logs: vec![],
+                    compute_units_consumed: None,
+                    outcome: None,
+                }),
+            },
+            _ => panic!("{:#?}", err),
+        }
+    }
+
+    fn simulate_transaction(&self, tx: Transaction) -> SimulationOutcome {
+        let account_keys = tx.message.account_keys.clone();
+        let result = self
+            .client
+            .simulate_transaction_with_config(
+                &tx,
+                RpcSimulateTransactionConfig {
+                    commitment: Some(self.client.commitment()),
+                    accounts: Some(RpcSimulateTransactionAccountsConfig {
+                        encoding: Some(UiAccountEncoding::Base64),
+                        addresses: account_keys.iter().map(Pubkey::to_string).collect(),
+                    }),
+                    ..RpcSimulateTransactionConfig::default()
                 },
             )
             .unwrap()
+            .value;
+        let accounts = result
+            .accounts
+            .map(|accounts| {
+                account_keys
+                    .into_iter()
+                    .zip(accounts)
+                    .map(|(pubkey, account)| {
+                        let account = account.map_or_else(Account::default, |account| {
+                            account.decode().expect("Failed to decode account")
+                        });
+                        (pubkey, account)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let return_data = result.return_data.map(|return_data| TransactionReturnData {
+            program_id: return_data
+                .program_id
+                .parse()
+                .expect("Failed to parse program id"),
+            data: base64::decode(&return_data.data.0).expect("Failed to decode return data"),
+        });
+        SimulationOutcome {
+            status: result.err.map_or(Ok(()), Err),
+            logs: result.logs.unwrap_or_default(),
+            accounts,
+            compute_units_consumed: result.units_consumed,
+            return_data,
+        }
     }
 
     fn get_latest_blockhash(&self) -> Hash {
@@ -977,6 +2123,34 @@ pub fn keypair(n: u8) -> Keypair {
     Keypair::from_bytes(&keys::KEYPAIRS[n as usize]).unwrap()
 }
 
+/// The address [Environment::deploy_program] deploys the program at the given path to, without deploying it.
+pub fn program_address<P: AsRef<Path>>(program_path: P) -> Pubkey {
+    program_keypair(&std::fs::read(program_path).unwrap()).pubkey()
+}
+
+/// The keypair of a program deployed by [Environment::deploy_program], derived from the program.
+fn program_keypair(data: &[u8]) -> Keypair {
+    let mut hash = Sha256::default();
+    hash.update(data);
+    let mut rng = StdRng::from_seed(hash.finalize()[..].try_into().unwrap());
+    Keypair::generate(&mut rng)
+}
+
+/// The number of buffers created by [Environment::create_program_buffer] in this process.
+static BUFFERS: AtomicU64 = AtomicU64::new(0);
+
+/// The keypair of the next buffer created by [Environment::create_program_buffer], derived from the program, the buffer authority
+/// and the number of buffers created before. Distinct from the program keypair of [Environment::deploy_upgradeable_program].
+fn buffer_keypair(data: &[u8], authority: &Pubkey) -> Keypair {
+    let mut hash = Sha256::default();
+    hash.update(b"buffer");
+    hash.update(data);
+    hash.update(authority.as_ref());
+    hash.update(BUFFERS.fetch_add(1, Ordering::Relaxed).to_le_bytes());
+    let mut rng = StdRng::from_seed(hash.finalize()[..].try_into().unwrap());
+    Keypair::generate(&mut rng)
+}
+
 /// Constructs a devnet client using `CommitmentConfig::confirmed()`.
 pub fn devnet_client() -> RpcClient {
     RpcClient::new_with_commitment(
@@ -0,0 +1,371 @@
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use solana_program::{
+    clock::Slot,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+use solana_sdk::{
+    account::Account,
+    transaction::{TransactionError, VersionedTransaction},
+    transaction_context::TransactionReturnData,
+};
+use solana_transaction_status::{
+    EncodedConfirmedTransactionWithStatusMeta, UiInstruction, UiTransactionStatusMeta,
+    UiTransactionTokenBalance,
+};
+
+use crate::{registry, registry::println_transaction, ExecutionProfile, PrintableTransaction};
+
+/// The change of the lamport balance of an account during a transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LamportChange {
+    pub pubkey: Pubkey,
+    pub pre: u64,
+    pub post: u64,
+}
+
+impl LamportChange {
+    /// The signed difference between the post and pre balance.
+    pub fn delta(&self) -> i128 {
+        self.post as i128 - self.pre as i128
+    }
+}
+
+/// The change of the balance of a token account during a transaction.
+/// Accounts that were created or closed by the transaction have a balance of zero on the missing side.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TokenChange {
+    pub pubkey: Pubkey,
+    pub mint: Pubkey,
+    pub owner: Option<Pubkey>,
+    pub decimals: u8,
+    pub pre: u64,
+    pub post: u64,
+}
+
+impl TokenChange {
+    /// The signed difference between the post and pre balance, in base units.
+    pub fn delta(&self) -> i128 {
+        self.post as i128 - self.pre as i128
+    }
+}
+
+/// The decoded result of executing a transaction. Returned by every [crate::Environment], no matter whether it is local or remote.
+#[derive(Clone, Debug)]
+pub struct ExecutionOutcome {
+    pub slot: Slot,
+    pub transaction: VersionedTransaction,
+    /// The error the transaction failed with, `Ok` if it succeeded.
+    pub status: Result<(), TransactionError>,
+    pub fee: u64,
+    pub logs: Vec<String>,
+    /// The instructions invoked through CPI, grouped by the index of the top-level instruction that invoked them.
+    /// The signer and writable flags are the ones the account has in the transaction.
+    pub inner_instructions: BTreeMap<u8, Vec<Instruction>>,
+    /// The compute units consumed by all top-level instructions. None if this couldn't be determined.
+    pub compute_units_consumed: Option<u64>,
+    pub return_data: Option<TransactionReturnData>,
+    /// The lamport balances of all accounts of the transaction, in the order of the account keys.
+    pub lamport_changes: Vec<LamportChange>,
+    /// The balances of all token accounts of the transaction.
+    pub token_changes: Vec<TokenChange>,
+    /// The profile of the invoked programs, if profiling is enabled with [crate::LocalEnvironment::set_profiling].
+    pub profile: Option<ExecutionProfile>,
+    meta: UiTransactionStatusMeta,
+}
+
+impl ExecutionOutcome {
+    /// Decodes a transaction as returned by the RPC `getTransaction` method with binary encoding.
+    pub fn from_encoded(encoded: EncodedConfirmedTransactionWithStatusMeta) -> Self {
+        let transaction = encoded
+            .transaction
+            .transaction
+            .decode()
+            .expect("Failed to decode transaction");
+        let meta = encoded
+            .transaction
+            .meta
+            .expect("transaction has no status meta");
+
+        let mut account_keys = transaction.message.static_account_keys().to_vec();
+        let mut writable = (0..account_keys.len())
+            .map(|i| transaction.message.is_maybe_writable(i))
+            .collect::<Vec<_>>();
+        if let Some(loaded) = &meta.loaded_addresses {
+            for (keys, is_writable) in [(&loaded.writable, true), (&loaded.readonly, false)] {
+                for key in keys {
+                    account_keys.push(parse_pubkey(key));
+                    writable.push(is_writable);
+                }
+            }
+        }
+
+        let account_meta = |index: u8| {
+            let index = index as usize;
+            AccountMeta {
+                pubkey: account_keys[index],
+                is_signer: transaction.message.is_signer(index),
+                is_writable: writable[index],
+            }
+        };
+        let inner_instructions = meta
+            .inner_instructions
+            .iter()
+            .flatten()
+            .map(|inner| {
+                let instructions = inner
+                    .instructions
+                    .iter()
+                    .map(|instruction| match instruction {
+                        UiInstruction::Compiled(compiled) => Instruction {
+                            program_id: account_keys[compiled.program_id_index as usize],
+                            accounts: compiled
+                                .accounts
+                                .iter()
+                                .copied()
+                                .map(account_meta)
+                                .collect(),
+                            data: bs58::decode(&compiled.data)
+                                .into_vec()
+                                .expect("Failed to decode instruction data"),
+                        },
+                        UiInstruction::Parsed(_) => {
+                            panic!("inner instructions must be encoded as compiled instructions")
+                        }
+                    })
+                    .collect();
+                (inner.index, instructions)
+            })
+            .collect();
+
+        let lamport_changes = account_keys
+            .iter()
+            .zip(meta.pre_balances.iter().zip(meta.post_balances.iter()))
+            .map(|(&pubkey, (&pre, &post))| LamportChange { pubkey, pre, post })
+            .collect();
+
+        let mut token_changes: Vec<TokenChange> = vec![];
+        let pre_token_balances = meta.pre_token_balances.iter().flatten();
+        let post_token_balances = meta.post_token_balances.iter().flatten();
+        for (balance, is_post) in pre_token_balances
+            .map(|b| (b, false))
+            .chain(post_token_balances.map(|b| (b, true)))
+        {
+            let change = token_change(&mut token_changes, &account_keys, balance);
+            let amount = balance
+                .ui_token_amount
+                .amount
+                .parse()
+                .expect("Failed to parse token amount");
+            if is_post {
+                change.post = amount;
+            } else {
+                change.pre = amount;
+            }
+        }
+
+        let logs = meta.log_messages.clone().unwrap_or_default();
+        ExecutionOutcome {
+            slot: encoded.slot,
+            transaction,
+            status: meta.status.clone(),
+            fee: meta.fee,
+            compute_units_consumed: compute_units_from_logs(&logs),
+            logs,
+            inner_instructions,
+            return_data: meta.return_data.clone(),
+            lamport_changes,
+            token_changes,
+            profile: None,
+            meta,
+        }
+    }
+
+    /// The status meta of the transaction, as returned by RPC.
+    pub fn status_meta(&self) -> &UiTransactionStatusMeta {
+        &self.meta
+    }
+
+    /// Returns whether the transaction executed successfully.
+    pub fn is_success(&self) -> bool {
+        self.status.is_ok()
+    }
+
+    /// The error the transaction failed with. None if it succeeded.
+    pub fn error(&self) -> Option<&TransactionError> {
+        self.status.as_ref().err()
+    }
+
+    /// The change of the lamport balance of the given account. Zero if the account is not part of the transaction.
+    pub fn lamport_delta(&self, pubkey: &Pubkey) -> i128 {
+        self.lamport_changes
+            .iter()
+            .find(|change| change.pubkey == *pubkey)
+            .map_or(0, LamportChange::delta)
+    }
+
+    /// The change of the balance of the given token account. Zero if the account is not a token account of the transaction.
+    pub fn token_delta(&self, pubkey: &Pubkey) -> i128 {
+        self.token_changes
+            .iter()
+            .find(|change| change.pubkey == *pubkey)
+            .map_or(0, TokenChange::delta)
+    }
+
+    /// Turns a failed outcome into a [TransactionFailure].
+    pub fn into_result(self) -> Result<ExecutionOutcome, TransactionFailure> {
+        match &self.status {
+            Ok(()) => Ok(self),
+            Err(error) => Err(TransactionFailure {
+                error: error.clone(),
+                logs: self.logs.clone(),
+                compute_units_consumed: self.compute_units_consumed,
+                outcome: Some(Box::new(self)),
+            }),
+        }
+    }
+}
+
+/// A transaction that failed, either during preflight simulation or during execution.
+#[derive(Clone, Debug)]
+pub struct TransactionFailure {
+    pub error: TransactionError,
+    pub logs: Vec<String>,
+    pub compute_units_consumed: Option<u64>,
+    /// The executed transaction. None if the transaction was rejected before it landed in a block, e.g. by preflight simulation.
+    pub outcome: Option<Box<ExecutionOutcome>>,
+}
+
+impl fmt::Display for TransactionFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "transaction failed: {}", self.error)?;
+        for log in &self.logs {
+            writeln!(f, "  {}", log)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for TransactionFailure {}
+
+/// The result of simulating a transaction. Nothing of the simulation is committed.
+#[derive(Clone, Debug)]
+pub struct SimulationOutcome {
+    /// The error the simulation failed with, `Ok` if it succeeded.
+    pub status: Result<(), TransactionError>,
+    pub logs: Vec<String>,
+    /// The state of all accounts of the transaction after the simulation, in the order of the account keys.
+    /// Empty if the transaction could not be loaded.
+    pub accounts: Vec<(Pubkey, Account)>,
+    pub compute_units_consumed: Option<u64>,
+    pub return_data: Option<TransactionReturnData>,
+}
+
+impl SimulationOutcome {
+    /// Returns whether the simulation succeeded.
+    pub fn is_success(&self) -> bool {
+        self.status.is_ok()
+    }
+
+    /// The error the simulation failed with. None if it succeeded.
+    pub fn error(&self) -> Option<&TransactionError> {
+        self.status.as_ref().err()
+    }
+
+    /// The state of the given account after the simulation. None if the account is not part of the transaction.
+    pub fn account(&self, pubkey: &Pubkey) -> Option<&Account> {
+        self.accounts
+            .iter()
+            .find(|(key, _)| key == pubkey)
+            .map(|(_, account)| account)
+    }
+}
+
+impl PrintableTransaction for SimulationOutcome {
+    fn print_named(&self, name: &str) {
+        println!("SIMULATE {}", name);
+        match &self.status {
+            Ok(()) => println!("  Status: Ok"),
+            Err(err) => println!("  Status: {}", err),
+        }
+        if let Some(units) = self.compute_units_consumed {
+            println!("  Compute Units Consumed: {}", units);
+        }
+        println!("  Log Messages:");
+        for log in &self.logs {
+            println!("    {}", registry::with_labels(log));
+        }
+    }
+
+    fn assert_success(&self) {
+        if self.status.is_err() {
+            self.print();
+            panic!("simulation failed!")
+        }
+    }
+}
+
+impl PrintableTransaction for ExecutionOutcome {
+    fn print_named(&self, name: &str) {
+        println!("EXECUTE {} (slot {})", name, self.slot);
+        println_transaction(&self.transaction, Some(&self.meta), "  ", None, None);
+    }
+
+    fn assert_success(&self) {
+        if self.status.is_err() {
+            self.print();
+            panic!("tx failed!")
+        }
+    }
+}
+
+fn parse_pubkey(key: &str) -> Pubkey {
+    Pubkey::from_str(key).expect("Failed to parse pubkey")
+}
+
+fn token_change<'a>(
+    changes: &'a mut Vec<TokenChange>,
+    account_keys: &[Pubkey],
+    balance: &UiTransactionTokenBalance,
+) -> &'a mut TokenChange {
+    let pubkey = account_keys[balance.account_index as usize];
+    match changes.iter().position(|change| change.pubkey == pubkey) {
+        Some(position) => &mut changes[position],
+        None => {
+            changes.push(TokenChange {
+                pubkey,
+                mint: parse_pubkey(&balance.mint),
+                owner: balance.owner.as_deref().map(parse_pubkey),
+                decimals: balance.ui_token_amount.decimals,
+                pre: 0,
+                post: 0,
+            });
+            changes.last_mut().unwrap()
+        }
+    }
+}
+
+/// Sums up the compute units reported by the runtime for each top-level instruction.
+/// Only lines of the form `Program <program id> ...` are written by the runtime, logs of the programs start with `Program log:`.
+fn compute_units_from_logs(logs: &[String]) -> Option<u64> {
+    let mut depth = 0usize;
+    let mut total = None;
+    for log in logs {
+        let words = log.split_whitespace().collect::<Vec<_>>();
+        match words[..] {
+            ["Program", program_id, ..] if Pubkey::from_str(program_id).is_err() => (),
+            ["Program", _, "invoke", _] => depth += 1,
+            ["Program", _, "success"] | ["Program", _, "failed:", ..] => {
+                depth = depth.saturating_sub(1)
+            }
+            ["Program", _, "consumed", units, "of", _, "compute", "units"] if depth == 1 => {
+                if let Ok(units) = units.parse::<u64>() {
+                    total = Some(total.unwrap_or(0) + units);
+                }
+            }
+            _ => (),
+        }
+    }
+    total
+}
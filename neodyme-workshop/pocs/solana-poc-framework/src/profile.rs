@@ -0,0 +1,477 @@
+//! Profiles the BPF programs invoked by a transaction.
+//! While profiling, the BPF loaders execute programs with an executor that has instruction tracing enabled,
+//! and the trace is used to count the syscalls of every invocation. The same executor records the traces of [crate::trace].
+
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use solana_bpf_loader_program::{
+    create_vm,
+    serialization::{deserialize_parameters, serialize_parameters},
+    syscalls::{register_syscalls, SyscallError},
+    BpfError, ThisInstructionMeter,
+};
+use solana_program::{
+    bpf_loader, bpf_loader_deprecated,
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+    entrypoint::SUCCESS,
+    instruction::InstructionError,
+    program_error::MAX_ACCOUNTS_DATA_SIZE_EXCEEDED,
+    pubkey::Pubkey,
+};
+use solana_program_runtime::{
+    ic_logger_msg,
+    invoke_context::{Executor, InvokeContext},
+    stable_log,
+    timings::ExecuteTimings,
+};
+use solana_rbpf::{
+    ebpf,
+    elf::Executable,
+    error::EbpfError,
+    verifier::RequisiteVerifier,
+    vm::{Config, VerifiedExecutable},
+};
+use solana_sdk::feature_set::{
+    cap_accounts_data_len, disable_bpf_unresolved_symbols_at_runtime,
+    error_on_syscall_bpf_function_hash_collisions, reject_callx_r10,
+};
+
+use crate::trace;
+
+thread_local! {
+    /// The invocations recorded so far, None if nothing is profiled.
+    static INVOCATIONS: RefCell<Option<Vec<InvocationProfile>>> = const { RefCell::new(None) };
+}
+
+/// One invocation of a BPF program, either by a top-level instruction or through CPI.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvocationProfile {
+    /// The index of the top-level instruction the invocation belongs to.
+    pub instruction_index: usize,
+    pub program_id: Pubkey,
+    /// 1 for top-level instructions, increased by one with every CPI.
+    pub depth: usize,
+    /// The compute units consumed, including the ones of the programs invoked through CPI.
+    pub compute_units: u64,
+    /// The number of BPF instructions executed by this invocation, without the ones of the programs invoked through CPI.
+    pub instructions: u64,
+    /// The syscalls of this invocation by name, without the ones of the programs invoked through CPI.
+    pub syscalls: BTreeMap<String, u64>,
+    /// The time spent executing, including the programs invoked through CPI.
+    pub duration: Duration,
+    pub status: Result<(), InstructionError>,
+}
+
+impl InvocationProfile {
+    /// The number of times the syscall with the given name was called, e.g. `sol_invoke_signed_rust`.
+    pub fn syscall_count(&self, name: &str) -> u64 {
+        self.syscalls.get(name).copied().unwrap_or(0)
+    }
+}
+
+/// The profile of a transaction, returned in [crate::ExecutionOutcome::profile] if profiling is enabled with
+/// [crate::LocalEnvironment::set_profiling].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExecutionProfile {
+    /// All invocations of BPF programs in the order they started. Builtin programs, like the system program, are not listed.
+    pub invocations: Vec<InvocationProfile>,
+    /// The time the runtime spent serializing the accounts for the programs.
+    pub serialize: Duration,
+    /// The time the runtime spent creating the VMs.
+    pub create_vm: Duration,
+    /// The time spent executing the programs.
+    pub execute: Duration,
+    /// The time the runtime spent deserializing the accounts changed by the programs.
+    pub deserialize: Duration,
+}
+
+impl ExecutionProfile {
+    /// The total number of calls of the syscall with the given name, over all invocations.
+    pub fn syscall_count(&self, name: &str) -> u64 {
+        self.invocations
+            .iter()
+            .map(|invocation| invocation.syscall_count(name))
+            .sum()
+    }
+
+    /// The deepest CPI level reached, 1 if no program was invoked through CPI.
+    pub fn max_depth(&self) -> usize {
+        self.invocations
+            .iter()
+            .map(|invocation| invocation.depth)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+impl fmt::Display for ExecutionProfile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "PROFILE")?;
+        let mut instruction_index = None;
+        for invocation in &self.invocations {
+            if instruction_index != Some(invocation.instruction_index) {
+                instruction_index = Some(invocation.instruction_index);
+                writeln!(f, "  Instruction {}", invocation.instruction_index)?;
+            }
+            let indent = "  ".repeat(invocation.depth + 1);
+            writeln!(
+                f,
+                "{}[{}] {}: {} CU, {} instructions, {:?}{}",
+                indent,
+                invocation.depth,
+                invocation.program_id,
+                invocation.compute_units,
+                invocation.instructions,
+                invocation.duration,
+                match &invocation.status {
+                    Ok(()) => String::new(),
+                    Err(err) => format!(", failed: {}", err),
+                }
+            )?;
+            for (name, count) in &invocation.syscalls {
+                writeln!(f, "{}      {}: {}", indent, name, count)?;
+            }
+        }
+        writeln!(
+            f,
+            "  Runtime: serialize {:?}, create vm {:?}, execute {:?}, deserialize {:?}",
+            self.serialize, self.create_vm, self.execute, self.deserialize
+        )
+    }
+}
+
+/// Starts recording the invocations of the current thread.
+pub(crate) fn begin() {
+    INVOCATIONS.with(|invocations| invocations.replace(Some(vec![])));
+}
+
+/// Stops recording and returns the profile of everything executed since [begin].
+pub(crate) fn finish(timings: &ExecuteTimings) -> ExecutionProfile {
+    let invocations = INVOCATIONS
+        .with(|invocations| invocations.replace(None))
+        .unwrap_or_default();
+    ExecutionProfile {
+        invocations,
+        serialize: Duration::from_micros(timings.details.serialize_us),
+        create_vm: Duration::from_micros(timings.details.create_vm_us),
+        execute: Duration::from_micros(timings.details.execute_us),
+        deserialize: Duration::from_micros(timings.details.deserialize_us),
+    }
+}
+
+fn is_profiling() -> bool {
+    INVOCATIONS.with(|invocations| invocations.borrow().is_some())
+}
+
+/// The entrypoint of the BPF loaders. While profiling or tracing, programs are executed by a [ProfilingExecutor].
+pub(crate) fn process_instruction(
+    first_instruction_account: usize,
+    invoke_context: &mut InvokeContext,
+) -> Result<(), InstructionError> {
+    if is_profiling() || trace::is_tracing() {
+        if let Some(elf) = program_elf(invoke_context) {
+            // The loader takes the executor from the cache, so it checks the program accounts as usual
+            if let Ok(executor) = ProfilingExecutor::new(&elf, invoke_context) {
+                let transaction_context = &invoke_context.transaction_context;
+                let instruction_context = transaction_context.get_current_instruction_context()?;
+                let program_id = *instruction_context.get_last_program_key(transaction_context)?;
+                invoke_context.add_executor(&program_id, Arc::new(executor));
+            }
+        }
+    }
+    solana_bpf_loader_program::process_instruction(first_instruction_account, invoke_context)
+}
+
+/// The ELF of the executed program, None if this is an instruction to the loader itself.
+fn program_elf(invoke_context: &InvokeContext) -> Option<Vec<u8>> {
+    let transaction_context = &invoke_context.transaction_context;
+    let instruction_context = transaction_context.get_current_instruction_context().ok()?;
+    let program = instruction_context
+        .try_borrow_last_program_account(transaction_context)
+        .ok()?;
+    if !program.is_executable() {
+        return None;
+    }
+    if bpf_loader::check_id(program.get_owner())
+        || bpf_loader_deprecated::check_id(program.get_owner())
+    {
+        Some(program.get_data().to_vec())
+    } else if bpf_loader_upgradeable::check_id(program.get_owner()) {
+        drop(program);
+        let programdata = instruction_context
+            .try_borrow_program_account(transaction_context, 0)
+            .ok()?;
+        match programdata.get_state().ok()? {
+            UpgradeableLoaderState::ProgramData { .. } => Some(
+                programdata
+                    .get_data()
+                    .get(UpgradeableLoaderState::size_of_programdata_metadata()..)?
+                    .to_vec(),
+            ),
+            _ => None,
+        }
+    } else {
+        None
+    }
+}
+
+/// Executes a program like the executor of the BPF loader, but records an [InvocationProfile] and an [crate::InvocationTrace].
+pub(crate) struct ProfilingExecutor {
+    verified_executable: VerifiedExecutable<RequisiteVerifier, BpfError, ThisInstructionMeter>,
+}
+
+impl fmt::Debug for ProfilingExecutor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ProfilingExecutor({:p})", self)
+    }
+}
+
+impl ProfilingExecutor {
+    /// Loads the program with the configuration of the BPF loader, plus instruction tracing and symbol names.
+    pub(crate) fn new(
+        elf: &[u8],
+        invoke_context: &mut InvokeContext,
+    ) -> Result<Self, EbpfError<BpfError>> {
+        let syscall_registry = register_syscalls(invoke_context, false)?;
+        let compute_budget = invoke_context.get_compute_budget();
+        let feature_set = &invoke_context.feature_set;
+        let config = Config {
+            max_call_depth: compute_budget.max_call_depth,
+            stack_frame_size: compute_budget.stack_frame_size,
+            enable_stack_frame_gaps: true,
+            instruction_meter_checkpoint_distance: 10000,
+            enable_instruction_meter: true,
+            enable_instruction_tracing: true,
+            enable_symbol_and_section_labels: true,
+            disable_unresolved_symbols_at_runtime: feature_set
+                .is_active(&disable_bpf_unresolved_symbols_at_runtime::id()),
+            reject_broken_elfs: false,
+            noop_instruction_rate: 256,
+            sanitize_user_provided_values: true,
+            encrypt_environment_registers: true,
+            disable_deprecated_load_instructions: false,
+            syscall_bpf_function_hash_collision: feature_set
+                .is_active(&error_on_syscall_bpf_function_hash_collisions::id()),
+            reject_callx_r10: feature_set.is_active(&reject_callx_r10::id()),
+            dynamic_stack_frames: false,
+            enable_sdiv: false,
+            optimize_rodata: false,
+            static_syscalls: false,
+            enable_elf_vaddr: false,
+        };
+        let executable =
+            Executable::<BpfError, ThisInstructionMeter>::from_elf(elf, config, syscall_registry)?;
+        Ok(ProfilingExecutor {
+            verified_executable: VerifiedExecutable::from_executable(executable)?,
+        })
+    }
+
+    /// Counts the syscalls in the instruction trace of an invocation by name.
+    fn count_syscalls(&self, trace: &[[u64; 12]]) -> BTreeMap<String, u64> {
+        let executable = self.verified_executable.get_executable();
+        let (_, text) = executable.get_text_bytes();
+        let names = executable.get_syscall_symbols();
+        let mut syscalls = BTreeMap::new();
+        for state in trace {
+            let insn = ebpf::get_insn_unchecked(text, state[11] as usize);
+            if insn.opc == ebpf::CALL_IMM {
+                if let Some(name) = names.get(&(insn.imm as u32)) {
+                    *syscalls.entry(name.clone()).or_insert(0) += 1;
+                }
+            }
+        }
+        syscalls
+    }
+}
+
+impl Executor for ProfilingExecutor {
+    fn execute(
+        &self,
+        _first_instruction_account: usize,
+        invoke_context: &mut InvokeContext,
+    ) -> Result<(), InstructionError> {
+        let log_collector = invoke_context.get_log_collector();
+        let compute_meter = invoke_context.get_compute_meter();
+        let stack_height = invoke_context.get_stack_height();
+        let transaction_context = &invoke_context.transaction_context;
+        let instruction_index = transaction_context
+            .get_instruction_trace()
+            .len()
+            .saturating_sub(1);
+        let instruction_context = transaction_context.get_current_instruction_context()?;
+        let program_id = *instruction_context.get_last_program_key(transaction_context)?;
+
+        // Recorded before the execution, so invocations through CPI come after the invoking one
+        let index = INVOCATIONS.with(|invocations| {
+            let mut invocations = invocations.borrow_mut();
+            let invocations = invocations.as_mut()?;
+            invocations.push(InvocationProfile {
+                instruction_index,
+                program_id,
+                depth: stack_height,
+                compute_units: 0,
+                instructions: 0,
+                syscalls: BTreeMap::new(),
+                duration: Duration::default(),
+                status: Ok(()),
+            });
+            Some(invocations.len() - 1)
+        });
+        let trace_index = trace::start_invocation(instruction_index, program_id, stack_height);
+
+        let serialize_time = Instant::now();
+        let (mut parameter_bytes, account_lengths) =
+            serialize_parameters(invoke_context.transaction_context, instruction_context)?;
+        let serialize_time = serialize_time.elapsed();
+
+        let create_vm_time = Instant::now();
+        let execute_time;
+        let execution_result = {
+            let mut vm = match create_vm(
+                &self.verified_executable,
+                parameter_bytes.as_slice_mut(),
+                account_lengths,
+                invoke_context,
+            ) {
+                Ok(info) => info,
+                Err(e) => {
+                    ic_logger_msg!(log_collector, "Failed to create BPF VM: {}", e);
+                    return Err(InstructionError::ProgramEnvironmentSetupFailure);
+                }
+            };
+            let create_vm_time = create_vm_time.elapsed();
+
+            execute_time = Instant::now();
+            stable_log::program_invoke(&log_collector, &program_id, stack_height);
+            let mut instruction_meter = ThisInstructionMeter {
+                compute_meter: compute_meter.clone(),
+            };
+            let before = compute_meter.borrow().get_remaining();
+            let result = vm.execute_program_interpreted(&mut instruction_meter);
+            let after = compute_meter.borrow().get_remaining();
+            let duration = execute_time.elapsed();
+            ic_logger_msg!(
+                log_collector,
+                "Program {} consumed {} of {} compute units",
+                &program_id,
+                before.saturating_sub(after),
+                before
+            );
+            let syscalls = self.count_syscalls(&vm.get_tracer().log);
+            let instructions = vm.get_total_instruction_count();
+            if let Some(index) = trace_index {
+                trace::finish_invocation(
+                    index,
+                    self.verified_executable.get_executable(),
+                    &vm.get_tracer().log,
+                );
+            }
+            drop(vm);
+            if let Some(index) = index {
+                INVOCATIONS.with(|invocations| {
+                    if let Some(invocation) = invocations
+                        .borrow_mut()
+                        .as_mut()
+                        .and_then(|invocations| invocations.get_mut(index))
+                    {
+                        invocation.compute_units = before.saturating_sub(after);
+                        invocation.instructions = instructions;
+                        invocation.syscalls = syscalls;
+                        invocation.duration = duration;
+                    }
+                });
+            }
+            let timings = &mut invoke_context.timings;
+            timings.create_vm_us = timings
+                .create_vm_us
+                .saturating_add(create_vm_time.as_micros() as u64);
+
+            let (_returned_from_program_id, return_data) =
+                invoke_context.transaction_context.get_return_data();
+            if !return_data.is_empty() {
+                stable_log::program_return(&log_collector, &program_id, return_data);
+            }
+            match result {
+                Ok(status) if status != SUCCESS => {
+                    let error: InstructionError = if status == MAX_ACCOUNTS_DATA_SIZE_EXCEEDED
+                        && !invoke_context
+                            .feature_set
+                            .is_active(&cap_accounts_data_len::id())
+                    {
+                        // Until the cap_accounts_data_len feature is enabled, map the
+                        // MAX_ACCOUNTS_DATA_SIZE_EXCEEDED error to InvalidError
+                        InstructionError::InvalidError
+                    } else {
+                        status.into()
+                    };
+                    stable_log::program_failure(&log_collector, &program_id, &error);
+                    Err(error)
+                }
+                Err(error) => {
+                    let error = match error {
+                        EbpfError::UserError(BpfError::SyscallError(
+                            SyscallError::InstructionError(error),
+                        )) => error,
+                        err => {
+                            ic_logger_msg!(log_collector, "Program failed to complete: {}", err);
+                            InstructionError::ProgramFailedToComplete
+                        }
+                    };
+                    stable_log::program_failure(&log_collector, &program_id, &error);
+                    Err(error)
+                }
+                _ => Ok(()),
+            }
+        };
+        let execute_time = execute_time.elapsed();
+
+        let deserialize_time = Instant::now();
+        let execute_or_deserialize_result = execution_result.and_then(|_| {
+            deserialize_parameters(
+                invoke_context.transaction_context,
+                invoke_context
+                    .transaction_context
+                    .get_current_instruction_context()?,
+                parameter_bytes.as_slice(),
+                invoke_context.get_orig_account_lengths()?,
+            )
+        });
+        let deserialize_time = deserialize_time.elapsed();
+
+        let timings = &mut invoke_context.timings;
+        timings.serialize_us = timings
+            .serialize_us
+            .saturating_add(serialize_time.as_micros() as u64);
+        timings.execute_us = timings
+            .execute_us
+            .saturating_add(execute_time.as_micros() as u64);
+        timings.deserialize_us = timings
+            .deserialize_us
+            .saturating_add(deserialize_time.as_micros() as u64);
+
+        if let (Some(index), Err(err)) = (index, &execute_or_deserialize_result) {
+            INVOCATIONS.with(|invocations| {
+                if let Some(invocation) = invocations
+                    .borrow_mut()
+                    .as_mut()
+                    .and_then(|invocations| invocations.get_mut(index))
+                {
+                    invocation.status = Err(err.clone());
+                }
+            });
+        }
+        if let (Some(index), Err(err)) = (trace_index, &execute_or_deserialize_result) {
+            trace::fail_invocation(index, err);
+        }
+        if execute_or_deserialize_result.is_ok() {
+            stable_log::program_success(&log_collector, &program_id);
+        }
+        execute_or_deserialize_result
+    }
+}
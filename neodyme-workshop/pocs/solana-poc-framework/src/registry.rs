@@ -0,0 +1,116 @@
+//! A registry of labelled keys. Transactions printed through [crate::PrintableTransaction] show the label of every
+//! registered address instead of its base58 representation.
+//!
+//! Keys can be loaded from and saved to keypair files in the JSON format of the Solana CLI, or derived from a seed phrase.
+//! Addresses without a keypair, like PDAs, are labelled with [register].
+//!
+//! ```ignore
+//! let hacker = registry::derive_keypair("pepper bolt arena ...", "hacker");
+//! let authority = registry::load_keypair("authority", "authority.json");
+//! registry::register("vault", level0::get_vault_address(authority.pubkey(), wallet_program));
+//! env.execute_as_transaction(&[...], &[&hacker]).print();
+//! ```
+
+use std::{collections::BTreeMap, fs, path::Path, sync::Mutex};
+
+use solana_cli_output::{display::writeln_transaction, CliSignatureVerificationStatus};
+use solana_program::{clock::UnixTimestamp, pubkey::Pubkey};
+use solana_sdk::{
+    signature::{
+        keypair_from_seed_phrase_and_passphrase, read_keypair_file, write_keypair_file, Keypair,
+    },
+    signer::Signer,
+    transaction::VersionedTransaction,
+};
+use solana_transaction_status::UiTransactionStatusMeta;
+
+/// The labels of all registered addresses. Shared by all threads, so the workers of the fuzzer print them too.
+static LABELS: Mutex<BTreeMap<Pubkey, String>> = Mutex::new(BTreeMap::new());
+
+/// Labels the address. A label belongs to one address at a time, registering it again moves it to the new address.
+pub fn register(label: &str, pubkey: Pubkey) {
+    let mut labels = LABELS.lock().unwrap();
+    labels.retain(|_, existing| existing != label);
+    labels.insert(pubkey, label.to_string());
+}
+
+/// The label of the address, None if it isn't registered.
+pub fn label(pubkey: &Pubkey) -> Option<String> {
+    LABELS.lock().unwrap().get(pubkey).cloned()
+}
+
+/// The address with the label, None if no address has it.
+pub fn pubkey(label: &str) -> Option<Pubkey> {
+    LABELS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|(_, existing)| *existing == label)
+        .map(|(pubkey, _)| *pubkey)
+}
+
+/// Removes all labels.
+pub fn clear() {
+    LABELS.lock().unwrap().clear();
+}
+
+/// Reads a keypair file written by `solana-keygen` and registers its address with the label.
+pub fn load_keypair<P: AsRef<Path>>(label: &str, path: P) -> Keypair {
+    let path = path.as_ref();
+    let keypair = read_keypair_file(path)
+        .unwrap_or_else(|err| panic!("couldn't read keypair {}: {}", path.display(), err));
+    register(label, keypair.pubkey());
+    keypair
+}
+
+/// Writes the keypair to a file the Solana CLI can use, creating the directory if necessary.
+pub fn save_keypair<P: AsRef<Path>>(keypair: &Keypair, path: P) {
+    let path = path.as_ref();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).expect("couldn't create keypair directory");
+    }
+    write_keypair_file(keypair, path)
+        .unwrap_or_else(|err| panic!("couldn't write keypair {}: {}", path.display(), err));
+}
+
+/// Derives the keypair for the label from the seed phrase and registers its address with the label.
+/// The label is used as passphrase, so `solana-keygen recover` derives the same keypair from the phrase and the label.
+pub fn derive_keypair(seed_phrase: &str, label: &str) -> Keypair {
+    let keypair = keypair_from_seed_phrase_and_passphrase(seed_phrase, label)
+        .expect("the seed is long enough");
+    register(label, keypair.pubkey());
+    keypair
+}
+
+/// Replaces every registered address in the text with its label.
+pub fn with_labels(text: &str) -> String {
+    let labels = LABELS.lock().unwrap();
+    labels
+        .iter()
+        .fold(text.to_string(), |text, (pubkey, label)| {
+            text.replace(&pubkey.to_string(), label)
+        })
+}
+
+/// Prints the transaction like `solana_cli_output::display::println_transaction`, with the registered addresses labelled.
+pub(crate) fn println_transaction(
+    transaction: &VersionedTransaction,
+    transaction_status: Option<&UiTransactionStatusMeta>,
+    prefix: &str,
+    sigverify_status: Option<&[CliSignatureVerificationStatus]>,
+    block_time: Option<UnixTimestamp>,
+) {
+    let mut text = String::new();
+    if writeln_transaction(
+        &mut text,
+        transaction,
+        transaction_status,
+        prefix,
+        sigverify_status,
+        block_time,
+    )
+    .is_ok()
+    {
+        print!("{}", with_labels(&text));
+    }
+}
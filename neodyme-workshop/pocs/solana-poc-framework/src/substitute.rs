@@ -0,0 +1,220 @@
+//! Searches for account confusion bugs by replacing the accounts of a legitimate instruction.
+//!
+//! Most programs trust some of the accounts they are given: they don't check the owner of an account, whether it is the
+//! expected PDA, the type of its data, or the address of a program they invoke. [LocalEnvironment::substitute_accounts]
+//! replaces one account of an instruction at a time with an account the attacker controls and executes every variant
+//! on a snapshot of the environment. A program should reject every variant; the accepted ones point to a missing check.
+//!
+//! ```ignore
+//! let withdraw = level0::withdraw(wallet_program, authority.pubkey(), wallet_address, amount);
+//! let report = env.substitute_accounts(&withdraw, &[&authority]);
+//! report.print();
+//! ```
+
+use std::{fmt, slice};
+
+use solana_program::{
+    hash::hashv,
+    instruction::{Instruction, InstructionError},
+    pubkey,
+    pubkey::Pubkey,
+};
+use solana_program_runtime::invoke_context::InvokeContext;
+use solana_sdk::signature::{keypair_from_seed, Keypair, Signer};
+
+use crate::{variants, Environment, ExecutionOutcome, LocalEnvironment};
+
+/// The address of the program that replaces programs and owns the copies with a different owner. It accepts every instruction.
+pub const FAKE_PROGRAM_ID: Pubkey = pubkey!("Fake111111111111111111111111111111111111111");
+
+/// How an account of the instruction was replaced.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Substitution {
+    /// A copy of the account at another address, with the same owner, lamports and data.
+    /// Accepted copies of program accounts point to a missing address or PDA check.
+    SameOwner,
+    /// A copy of the account owned by [FAKE_PROGRAM_ID], which can write any data to it. Accepted ones point to a missing owner check.
+    DifferentOwner,
+    /// [FAKE_PROGRAM_ID] instead of a program. Accepted ones point to a missing program id check before a CPI.
+    FakeProgram,
+    /// The account of another slot of the instruction, by index. Accepted ones point to a missing check for duplicate accounts.
+    Duplicate(usize),
+}
+
+impl fmt::Display for Substitution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Substitution::SameOwner => write!(f, "a copy with the same owner"),
+            Substitution::DifferentOwner => write!(f, "a copy owned by the fake program"),
+            Substitution::FakeProgram => write!(f, "the fake program"),
+            Substitution::Duplicate(index) => write!(f, "account {}", index),
+        }
+    }
+}
+
+/// An instruction with one account replaced, executed by [LocalEnvironment::substitute_accounts].
+#[derive(Clone, Debug)]
+pub struct Variant {
+    /// The index of the replaced account in the instruction.
+    pub index: usize,
+    pub substitution: Substitution,
+    pub original: Pubkey,
+    pub replacement: Pubkey,
+    pub outcome: ExecutionOutcome,
+}
+
+impl Variant {
+    /// Returns whether the program accepted the substituted account.
+    pub fn accepted(&self) -> bool {
+        self.outcome.status.is_ok()
+    }
+}
+
+/// The result of [LocalEnvironment::substitute_accounts].
+#[derive(Clone, Debug)]
+pub struct SubstitutionReport {
+    pub instruction: Instruction,
+    /// The outcome of the unchanged instruction. If it failed, the rejected variants don't tell anything.
+    pub original: ExecutionOutcome,
+    pub variants: Vec<Variant>,
+}
+
+impl SubstitutionReport {
+    /// The variants the program accepted.
+    pub fn accepted(&self) -> impl Iterator<Item = &Variant> {
+        self.variants.iter().filter(|variant| variant.accepted())
+    }
+
+    /// Prints every variant. FAIL means the program accepted the substituted account.
+    pub fn print(&self) {
+        variants::print_header(
+            format_args!(
+                "SUBSTITUTIONS: {} variants of an instruction of program {}",
+                self.variants.len(),
+                self.instruction.program_id
+            ),
+            &self.original,
+        );
+        for variant in &self.variants {
+            variants::print_variant(
+                "FAIL",
+                format_args!(
+                    "account {} ({}) replaced by {} ({})",
+                    variant.index, variant.original, variant.substitution, variant.replacement
+                ),
+                &variant.outcome,
+            );
+        }
+        let accepted = self.accepted().count();
+        variants::print_result(
+            accepted,
+            "all substitutions were rejected",
+            format_args!("{} substitutions were accepted", accepted),
+        );
+    }
+}
+
+impl LocalEnvironment {
+    /// Executes the instruction with every account replaced by an account the attacker controls, one at a time, see the [module docs](self).
+    /// Programs are replaced by a fake program, all other accounts by copies with the same and with a different owner.
+    /// Every account is also replaced by each of the other accounts of the instruction.
+    /// Replaced signers are signed for by the attacker.
+    ///
+    /// Every variant is executed on a snapshot of the current state, and the environment is rolled back to it afterwards.
+    /// Invariants are not checked for the variants.
+    pub fn substitute_accounts(
+        &mut self,
+        instruction: &Instruction,
+        signers: &[&Keypair],
+    ) -> SubstitutionReport {
+        let accounts = instruction
+            .accounts
+            .iter()
+            .map(|meta| self.get_account(meta.pubkey))
+            .collect::<Vec<_>>();
+
+        let mut variants = vec![];
+        for (index, (meta, account)) in instruction.accounts.iter().zip(&accounts).enumerate() {
+            let substitutions = match account {
+                Some(account) if account.executable => vec![Substitution::FakeProgram],
+                Some(_) => vec![Substitution::SameOwner, Substitution::DifferentOwner],
+                None => vec![Substitution::SameOwner],
+            };
+            let duplicates = instruction
+                .accounts
+                .iter()
+                .enumerate()
+                .filter(|(_, other)| other.pubkey != meta.pubkey)
+                .map(|(other, _)| Substitution::Duplicate(other));
+
+            for substitution in substitutions.into_iter().chain(duplicates) {
+                let replacement = match substitution {
+                    Substitution::SameOwner | Substitution::DifferentOwner => {
+                        substitute_keypair(&meta.pubkey, &substitution).pubkey()
+                    }
+                    Substitution::FakeProgram => FAKE_PROGRAM_ID,
+                    Substitution::Duplicate(other) => instruction.accounts[other].pubkey,
+                };
+                variants.push((index, substitution, meta.pubkey, replacement));
+            }
+        }
+
+        let (original, outcomes) = self.run_variants(
+            slice::from_ref(instruction),
+            signers,
+            variants,
+            |env, &(index, substitution, original, replacement)| {
+                env.bank()
+                    .add_builtin("fake program", &FAKE_PROGRAM_ID, accept);
+                let attacker = substitute_keypair(&original, &substitution);
+                if let (Substitution::SameOwner | Substitution::DifferentOwner, Some(mut copy)) =
+                    (substitution, accounts[index].clone())
+                {
+                    if substitution == Substitution::DifferentOwner {
+                        copy.owner = FAKE_PROGRAM_ID;
+                    }
+                    env.set_account(attacker.pubkey(), copy);
+                }
+
+                let mut variant = instruction.clone();
+                variant.accounts[index].pubkey = replacement;
+                // Duplicates of accounts nobody can sign for can't be signed and are skipped
+                Some((vec![variant], vec![attacker]))
+            },
+        );
+        let variants = outcomes
+            .into_iter()
+            .map(
+                |((index, substitution, original, replacement), outcome)| Variant {
+                    index,
+                    substitution,
+                    original,
+                    replacement,
+                    outcome,
+                },
+            )
+            .collect();
+        SubstitutionReport {
+            instruction: instruction.clone(),
+            original,
+            variants,
+        }
+    }
+}
+
+/// The keypair of the account replacing the given one, the same on every run.
+fn substitute_keypair(original: &Pubkey, substitution: &Substitution) -> Keypair {
+    let seed = hashv(&[
+        b"substitute",
+        original.as_ref(),
+        substitution.to_string().as_bytes(),
+    ]);
+    keypair_from_seed(seed.as_ref()).expect("the seed is long enough")
+}
+
+fn accept(
+    _first_instruction_account: usize,
+    _invoke_context: &mut InvokeContext,
+) -> Result<(), InstructionError> {
+    Ok(())
+}
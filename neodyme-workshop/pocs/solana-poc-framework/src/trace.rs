@@ -0,0 +1,251 @@
+//! Instruction-level traces of the BPF programs invoked by a transaction.
+//! While tracing, the BPF loaders execute programs with the executor of the profiler, which has instruction tracing enabled.
+//! The register states it records are resolved against the disassembly and the symbols of the ELF.
+
+use std::{cell::RefCell, collections::BTreeMap, fmt, fs::File, io::Write, path::Path};
+
+use serde::{Deserialize, Serialize};
+use solana_bpf_loader_program::{BpfError, ThisInstructionMeter};
+use solana_program::{instruction::InstructionError, pubkey::Pubkey};
+use solana_rbpf::{
+    disassembler::disassemble_instruction, ebpf, elf::Executable, static_analysis::Analysis,
+};
+
+thread_local! {
+    /// The invocations traced so far, None if nothing is traced.
+    static TRACES: RefCell<Option<Vec<InvocationTrace>>> = const { RefCell::new(None) };
+}
+
+/// One executed BPF instruction.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceStep {
+    /// The index of the instruction in the text section. `cargo build-bpf --dump` shows it increased by 29.
+    pub pc: u64,
+    /// The registers r0 to r10 before the instruction was executed.
+    pub registers: [u64; 11],
+    /// The disassembled instruction, e.g. `syscall sol_log_` or `call process_instruction`.
+    pub instruction: String,
+    /// The demangled name of the function the instruction belongs to. Stripped programs only have generated names like `function_2503`.
+    pub function: Option<String>,
+}
+
+/// The instructions executed by one invocation of a BPF program, either by a top-level instruction or through CPI.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InvocationTrace {
+    /// The index of the top-level instruction the invocation belongs to.
+    pub instruction_index: usize,
+    pub program_id: Pubkey,
+    /// 1 for top-level instructions, increased by one with every CPI.
+    pub depth: usize,
+    /// The executed instructions, without the ones of the programs invoked through CPI.
+    pub steps: Vec<TraceStep>,
+    pub status: Result<(), InstructionError>,
+}
+
+/// The trace of a transaction, returned by [crate::LocalEnvironment::execute_transaction_traced].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecutionTrace {
+    /// All invocations of BPF programs in the order they started. Builtin programs, like the system program, are not traced.
+    pub invocations: Vec<InvocationTrace>,
+}
+
+impl ExecutionTrace {
+    /// All invocations of the given program.
+    pub fn invocations_of(&self, program_id: &Pubkey) -> impl Iterator<Item = &InvocationTrace> {
+        let program_id = *program_id;
+        self.invocations
+            .iter()
+            .filter(move |invocation| invocation.program_id == program_id)
+    }
+
+    /// Writes the trace as text, one line per executed instruction, so it can be grepped and diffed.
+    pub fn save<P: AsRef<Path>>(&self, path: P) {
+        let mut file = File::create(path).expect("couldn't create trace");
+        write!(file, "{}", self).expect("couldn't write trace");
+    }
+}
+
+impl fmt::Display for InvocationTrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "[{}] {}: {} instructions{}",
+            self.depth,
+            self.program_id,
+            self.steps.len(),
+            match &self.status {
+                Ok(()) => String::new(),
+                Err(err) => format!(", failed: {}", err),
+            }
+        )?;
+        let mut function = None;
+        for (index, step) in self.steps.iter().enumerate() {
+            if let Some(name) = &step.function {
+                if function != Some(name) {
+                    function = Some(name);
+                    writeln!(f, "{}:", name)?;
+                }
+            }
+            writeln!(
+                f,
+                "{:6} {:016X?} {:5}: {}",
+                index,
+                step.registers,
+                step.pc + ebpf::ELF_INSN_DUMP_OFFSET as u64,
+                step.instruction
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ExecutionTrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "TRACE")?;
+        for invocation in &self.invocations {
+            writeln!(f, "Instruction {}", invocation.instruction_index)?;
+            write!(f, "{}", invocation)?;
+        }
+        Ok(())
+    }
+}
+
+/// Starts tracing the invocations of the current thread.
+pub(crate) fn begin() {
+    TRACES.with(|traces| traces.replace(Some(vec![])));
+}
+
+/// Stops tracing and returns everything executed since [begin].
+pub(crate) fn finish() -> ExecutionTrace {
+    ExecutionTrace {
+        invocations: TRACES
+            .with(|traces| traces.replace(None))
+            .unwrap_or_default(),
+    }
+}
+
+pub(crate) fn is_tracing() -> bool {
+    TRACES.with(|traces| traces.borrow().is_some())
+}
+
+/// Records the start of an invocation. Returns its index, None if nothing is traced.
+pub(crate) fn start_invocation(
+    instruction_index: usize,
+    program_id: Pubkey,
+    depth: usize,
+) -> Option<usize> {
+    TRACES.with(|traces| {
+        let mut traces = traces.borrow_mut();
+        let traces = traces.as_mut()?;
+        traces.push(InvocationTrace {
+            instruction_index,
+            program_id,
+            depth,
+            steps: vec![],
+            status: Ok(()),
+        });
+        Some(traces.len() - 1)
+    })
+}
+
+/// Stores the steps of an invocation, resolved from the log of the rbpf tracer.
+pub(crate) fn finish_invocation(
+    index: usize,
+    executable: &Executable<BpfError, ThisInstructionMeter>,
+    log: &[[u64; 12]],
+) {
+    let steps = steps(executable, log);
+    with_invocation(index, |invocation| invocation.steps = steps);
+}
+
+/// Marks an invocation as failed.
+pub(crate) fn fail_invocation(index: usize, error: &InstructionError) {
+    with_invocation(index, |invocation| invocation.status = Err(error.clone()));
+}
+
+fn with_invocation(index: usize, f: impl FnOnce(&mut InvocationTrace)) {
+    TRACES.with(|traces| {
+        if let Some(invocation) = traces
+            .borrow_mut()
+            .as_mut()
+            .and_then(|traces| traces.get_mut(index))
+        {
+            f(invocation);
+        }
+    });
+}
+
+fn steps(
+    executable: &Executable<BpfError, ThisInstructionMeter>,
+    log: &[[u64; 12]],
+) -> Vec<TraceStep> {
+    let analysis = match analyze(executable) {
+        Some(analysis) => analysis,
+        None => return vec![],
+    };
+    // lddw spans two slots of the text section
+    let mut pc_to_insn_index = BTreeMap::new();
+    for (index, insn) in analysis.instructions.iter().enumerate() {
+        pc_to_insn_index.insert(insn.ptr, index);
+    }
+    let function_names: BTreeMap<usize, &str> = analysis
+        .functions
+        .keys()
+        .filter_map(|pc| Some((*pc, analysis.cfg_nodes.get(pc)?.label.as_str())))
+        .collect();
+
+    log.iter()
+        .map(|entry| {
+            let pc = entry[11] as usize;
+            let mut registers = [0; 11];
+            registers.copy_from_slice(&entry[0..11]);
+            TraceStep {
+                pc: pc as u64,
+                registers,
+                instruction: pc_to_insn_index
+                    .get(&pc)
+                    .map(|index| disassemble_instruction(&analysis.instructions[*index], &analysis))
+                    .unwrap_or_else(|| "[invalid]".to_string()),
+                function: function_names
+                    .range(..=pc)
+                    .next_back()
+                    .map(|(_, name)| name.to_string()),
+            }
+        })
+        .collect()
+}
+
+/// The labels of the instructions, without the data flow analysis of [Analysis::from_executable], which takes seconds.
+fn analyze(
+    executable: &Executable<BpfError, ThisInstructionMeter>,
+) -> Option<Analysis<'_, BpfError, ThisInstructionMeter>> {
+    let (_, text) = executable.get_text_bytes();
+    let mut instructions = Vec::with_capacity(text.len() / ebpf::INSN_SIZE);
+    let mut insn_ptr = 0;
+    while (insn_ptr + 1) * ebpf::INSN_SIZE <= text.len() {
+        let mut insn = ebpf::get_insn_unchecked(text, insn_ptr);
+        if insn.opc == ebpf::LD_DW_IMM {
+            insn_ptr += 1;
+            if (insn_ptr + 1) * ebpf::INSN_SIZE > text.len() {
+                break;
+            }
+            ebpf::augment_lddw_unchecked(text, &mut insn);
+        }
+        instructions.push(insn);
+        insn_ptr += 1;
+    }
+    let mut analysis = Analysis {
+        executable,
+        instructions,
+        functions: executable.get_function_symbols(),
+        cfg_nodes: BTreeMap::new(),
+        topological_order: vec![],
+        entrypoint: executable.get_entrypoint_instruction_offset().ok()?,
+        super_root: insn_ptr,
+        dfg_forward_edges: BTreeMap::new(),
+        dfg_reverse_edges: BTreeMap::new(),
+    };
+    analysis.split_into_basic_blocks(false);
+    analysis.label_basic_blocks();
+    Some(analysis)
+}
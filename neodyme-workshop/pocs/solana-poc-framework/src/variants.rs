@@ -0,0 +1,85 @@
+//! The driver shared by the checks that execute variants of a transaction on a snapshot, like [crate::flags] and [crate::substitute].
+
+use std::fmt;
+
+use solana_program::instruction::Instruction;
+use solana_sdk::signature::Keypair;
+
+use crate::{Environment, ExecutionOutcome, LocalEnvironment};
+
+impl LocalEnvironment {
+    /// Executes the instructions, then every variant of them on a snapshot of the current state, and rolls the environment
+    /// back to the snapshot afterwards. Invariants are not checked.
+    ///
+    /// `prepare` is called for every variant after the state was restored. It may change the environment, e.g. to add accounts,
+    /// and returns the instructions of the variant and the keypairs that sign it in addition to the given signers.
+    /// Variants it returns None for and variants that can't be signed are skipped.
+    pub(crate) fn run_variants<V, F>(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+        variants: Vec<V>,
+        mut prepare: F,
+    ) -> (ExecutionOutcome, Vec<(V, ExecutionOutcome)>)
+    where
+        F: FnMut(&mut Self, &V) -> Option<(Vec<Instruction>, Vec<Keypair>)>,
+    {
+        let invariants = std::mem::take(&mut self.invariants);
+        let snapshot = self.snapshot();
+
+        let original = match self.sign_instructions(instructions, signers) {
+            Some(tx) => self.execute_transaction(tx),
+            None => panic!("missing signers for the original transaction"),
+        };
+
+        let mut outcomes = vec![];
+        for variant in variants {
+            self.restore(&snapshot);
+            let (instructions, extra_signers) = match prepare(self, &variant) {
+                Some(prepared) => prepared,
+                None => continue,
+            };
+            let mut variant_signers = signers.to_vec();
+            variant_signers.extend(&extra_signers);
+            let tx = match self.sign_instructions(&instructions, &variant_signers) {
+                Some(tx) => tx,
+                None => continue,
+            };
+            outcomes.push((variant, self.execute_transaction(tx)));
+        }
+
+        self.restore(&snapshot);
+        self.invariants = invariants;
+        (original, outcomes)
+    }
+}
+
+/// Prints the header of a report of variants, and the error of the original transaction if it failed.
+pub(crate) fn print_header(title: fmt::Arguments, original: &ExecutionOutcome) {
+    println!("{}", title);
+    if let Err(err) = &original.status {
+        println!("  the original transaction failed: {}", err);
+    }
+}
+
+/// Prints a variant as `[STATUS] description: result`. Accepted variants get the given status, rejected ones PASS and the error.
+pub(crate) fn print_variant(
+    accepted_status: &str,
+    description: fmt::Arguments,
+    outcome: &ExecutionOutcome,
+) {
+    let (status, result) = match &outcome.status {
+        Ok(()) => (accepted_status, "accepted".to_string()),
+        Err(err) => ("PASS", err.to_string()),
+    };
+    println!("  [{}] {}: {}", status, description, result);
+}
+
+/// Prints the RESULT line of a report, FAIL if any variant failed.
+pub(crate) fn print_result(failed: usize, pass: &str, fail: fmt::Arguments) {
+    if failed == 0 {
+        println!("RESULT: PASS, {}", pass);
+    } else {
+        println!("RESULT: FAIL, {}", fail);
+    }
+}
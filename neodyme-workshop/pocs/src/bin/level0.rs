@@ -1,30 +1,17 @@
-use std::{env, str::FromStr};
-
-use owo_colors::OwoColorize;
+use std::str::FromStr;
 
 use poc_framework::solana_sdk::signature::Keypair;
 use poc_framework::{
-    keypair, solana_sdk::signer::Signer, Environment, LocalEnvironment, PrintableTransaction,
+    assert::Invariant, keypair, processor,
+    solana_program_runtime::invoke_context::ProcessInstructionWithContext,
+    solana_sdk::signer::Signer, Environment, LocalEnvironment, PrintableTransaction,
 };
+use pocs::{Challenge, Program};
 
-use solana_program::native_token::lamports_to_sol;
 use solana_program::{native_token::sol_to_lamports, pubkey::Pubkey, system_program};
 
-use poc_framework::solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
-
-pub fn assert_tx_success(
-    tx: EncodedConfirmedTransactionWithStatusMeta,
-) -> EncodedConfirmedTransactionWithStatusMeta {
-    match &tx.transaction.meta {
-        Some(meta) if meta.err.is_some() => {
-            tx.print();
-            panic!("tx failed!")
-        }
-        _ => tx,
-    }
-}
-
-struct Challenge {
+#[allow(dead_code)]
+struct Level0 {
     hacker: Keypair,
     wallet_program: Pubkey,
     wallet_address: Pubkey,
@@ -32,146 +19,100 @@ struct Challenge {
     wallet_authority: Pubkey,
 }
 
-// Do your hacks in this function here
-fn hack(_env: &mut LocalEnvironment, _challenge: &Challenge) {}
+impl Challenge for Level0 {
+    const PROGRAM: &'static str = "level0";
+    const PROCESSOR: ProcessInstructionWithContext =
+        processor!(level0::processor::process_instruction);
 
-/*
-SETUP CODE BELOW
-*/
-pub fn main() {
-    let (mut env, challenge, internal) = setup();
-    let before_balance = env.get_account(challenge.hacker.pubkey()).unwrap().lamports;
-    hack(&mut env, &challenge);
-    verify(&mut env, challenge, before_balance, internal);
-}
+    // Do your hacks in this function here
+    fn hack(&self, _env: &mut LocalEnvironment) {}
 
-struct Internal {
-    wallet_authority: Keypair,
-    wallet_amount: u64,
-}
+    fn attackers(&self) -> Vec<Pubkey> {
+        vec![self.hacker.pubkey()]
+    }
 
-fn verify(
-    env: &mut LocalEnvironment,
-    challenge: Challenge,
-    before_balance: u64,
-    internal: Internal,
-) {
-    let after = env.get_account(challenge.hacker.pubkey()).unwrap().lamports;
-
-    let tx = env.execute_as_transaction(
-        &[level0::withdraw(
-            challenge.wallet_program,
-            challenge.wallet_authority,
-            challenge.wallet_address,
-            internal.wallet_amount,
-        )],
-        &[&internal.wallet_authority],
-    );
-    tx.print_named("Verification: authority withdraw");
-
-    if tx.transaction.meta.unwrap().err.is_none() {
-        println!(
-            "[*] {}",
-            "Original wallet authority successfully withdrew all funds.".red()
+    /*
+    SETUP CODE BELOW
+    */
+    fn setup(program: &Program) -> (LocalEnvironment, Self) {
+        let wallet_program =
+            Pubkey::from_str("W4113t3333333333333333333333333333333333333").unwrap();
+        let wallet_authority = keypair(0);
+        let rich_boi = keypair(1);
+        let hacker = keypair(42);
+
+        let a_lot_of_money = sol_to_lamports(1_000_000.0);
+
+        let mut builder = LocalEnvironment::builder();
+        program.add_to(&mut builder, wallet_program);
+        let mut env = builder
+            .add_account_with_lamports(
+                wallet_authority.pubkey(),
+                system_program::ID,
+                sol_to_lamports(100.0),
+            )
+            .add_account_with_lamports(rich_boi.pubkey(), system_program::ID, a_lot_of_money * 2)
+            .add_account_with_lamports(hacker.pubkey(), system_program::ID, sol_to_lamports(1.0))
+            .build();
+
+        let wallet_address = level0::get_wallet_address(wallet_authority.pubkey(), wallet_program);
+
+        let vault_address = level0::get_vault_address(wallet_authority.pubkey(), wallet_program);
+
+        // Create Wallet
+        env.execute_as_transaction(
+            &[level0::initialize(
+                wallet_program,
+                wallet_authority.pubkey(),
+            )],
+            &[&wallet_authority],
         )
-    } else {
-        println!(
-            "[!] {}",
-            "Original wallet authority cannot withdraw his funds anymore.".green()
+        .assert_success();
+
+        println!("[*] Wallet created!");
+
+        // rich boi pays for bill
+        env.execute_as_transaction(
+            &[level0::deposit(
+                wallet_program,
+                wallet_authority.pubkey(),
+                rich_boi.pubkey(),
+                a_lot_of_money,
+            )],
+            &[&rich_boi],
+        )
+        .assert_success();
+        println!("[*] rich boi payed his bills");
+
+        env.add_invariant(Invariant::can_execute(
+            "wallet authority can withdraw all funds",
+            &[level0::withdraw(
+                wallet_program,
+                wallet_authority.pubkey(),
+                wallet_address,
+                a_lot_of_money,
+            )],
+            &[&wallet_authority],
+        ));
+        env.add_invariant(Invariant::program_lamports_never_decrease(
+            &env,
+            wallet_program,
+            &[wallet_authority.pubkey()],
+        ));
+
+        (
+            env,
+            Level0 {
+                wallet_address,
+                vault_address,
+                hacker,
+                wallet_program,
+                wallet_authority: wallet_authority.pubkey(),
+            },
         )
-    }
-
-    if after > before_balance {
-        println!("[*] {}", "Congratulations, the exploit succeeded!".green());
-        println!(
-            "[*] Hacker has {} SOL more! ({} vs {})",
-            lamports_to_sol(after - before_balance).green(),
-            before_balance,
-            after
-        );
-    } else {
-        println!("[*] {}", "Exploit not successful.".red());
-        println!(
-            "[*] Hacker has {} SOL less! ({} vs {})",
-            lamports_to_sol(before_balance - after).red(),
-            before_balance,
-            after
-        );
     }
 }
 
-fn setup() -> (LocalEnvironment, Challenge, Internal) {
-    let mut dir = env::current_exe().unwrap();
-    let path = {
-        dir.pop();
-        dir.pop();
-        dir.pop();
-        dir.pop();
-        dir.push("contracts");
-        dir.push("target");
-        dir.push("deploy");
-        dir.push("level0.so");
-        dir.to_str()
-    }
-    .unwrap();
-
-    let wallet_program = Pubkey::from_str("W4113t3333333333333333333333333333333333333").unwrap();
-    let wallet_authority = keypair(0);
-    let rich_boi = keypair(1);
-    let hacker = keypair(42);
-
-    let a_lot_of_money = sol_to_lamports(1_000_000.0);
-
-    let mut env = LocalEnvironment::builder()
-        .add_program(wallet_program, path)
-        .add_account_with_lamports(
-            wallet_authority.pubkey(),
-            system_program::ID,
-            sol_to_lamports(100.0),
-        )
-        .add_account_with_lamports(rich_boi.pubkey(), system_program::ID, a_lot_of_money * 2)
-        .add_account_with_lamports(hacker.pubkey(), system_program::ID, sol_to_lamports(1.0))
-        .build();
-
-    let wallet_address = level0::get_wallet_address(wallet_authority.pubkey(), wallet_program);
-
-    let vault_address = level0::get_vault_address(wallet_authority.pubkey(), wallet_program);
-
-    // Create Wallet
-    assert_tx_success(env.execute_as_transaction(
-        &[level0::initialize(
-            wallet_program,
-            wallet_authority.pubkey(),
-        )],
-        &[&wallet_authority],
-    ));
-
-    println!("[*] Wallet created!");
-
-    // rich boi pays for bill
-    assert_tx_success(env.execute_as_transaction(
-        &[level0::deposit(
-            wallet_program,
-            wallet_authority.pubkey(),
-            rich_boi.pubkey(),
-            a_lot_of_money,
-        )],
-        &[&rich_boi],
-    ));
-    println!("[*] rich boi payed his bills");
-
-    (
-        env,
-        Challenge {
-            wallet_address,
-            vault_address,
-            hacker,
-            wallet_program,
-            wallet_authority: wallet_authority.pubkey(),
-        },
-        Internal {
-            wallet_authority,
-            wallet_amount: a_lot_of_money,
-        },
-    )
+pub fn main() {
+    pocs::run::<Level0>();
 }
@@ -0,0 +1,118 @@
+use std::str::FromStr;
+
+use poc_framework::solana_sdk::signature::Keypair;
+use poc_framework::{
+    assert::Invariant, keypair, processor,
+    solana_program_runtime::invoke_context::ProcessInstructionWithContext,
+    solana_sdk::signer::Signer, Environment, LocalEnvironment, PrintableTransaction,
+};
+use pocs::{Challenge, Program};
+
+use solana_program::{native_token::sol_to_lamports, pubkey::Pubkey, system_program};
+
+#[allow(dead_code)]
+struct Level1 {
+    hacker: Keypair,
+    wallet_program: Pubkey,
+    wallet_address: Pubkey,
+    wallet_authority: Pubkey,
+}
+
+impl Challenge for Level1 {
+    const PROGRAM: &'static str = "level1";
+    const PROCESSOR: ProcessInstructionWithContext =
+        processor!(level1::processor::process_instruction);
+
+    // Do your hacks in this function here
+    fn hack(&self, _env: &mut LocalEnvironment) {}
+
+    fn attackers(&self) -> Vec<Pubkey> {
+        vec![self.hacker.pubkey()]
+    }
+
+    /*
+    SETUP CODE BELOW
+    */
+    fn setup(program: &Program) -> (LocalEnvironment, Self) {
+        let wallet_program =
+            Pubkey::from_str("W4113t3333333333333333333333333333333333333").unwrap();
+        let wallet_authority = keypair(0);
+        let rich_boi = keypair(1);
+        let hacker = keypair(42);
+
+        let a_lot_of_money = sol_to_lamports(42.0);
+
+        let mut builder = LocalEnvironment::builder();
+        program.add_to(&mut builder, wallet_program);
+        let mut env = builder
+            .add_account_with_lamports(
+                wallet_authority.pubkey(),
+                system_program::ID,
+                sol_to_lamports(100.0),
+            )
+            .add_account_with_lamports(
+                rich_boi.pubkey(),
+                system_program::ID,
+                sol_to_lamports(100.0),
+            )
+            .add_account_with_lamports(hacker.pubkey(), system_program::ID, sol_to_lamports(1.0))
+            .build();
+
+        let wallet_address = level1::get_wallet_address(wallet_authority.pubkey(), wallet_program);
+
+        // Create Wallet
+        env.execute_as_transaction(
+            &[level1::initialize(
+                wallet_program,
+                wallet_authority.pubkey(),
+            )],
+            &[&wallet_authority],
+        )
+        .assert_success();
+
+        println!("[*] Wallet created!");
+
+        // rich boi pays for bill
+        env.execute_as_transaction(
+            &[level1::deposit(
+                wallet_program,
+                wallet_authority.pubkey(),
+                rich_boi.pubkey(),
+                a_lot_of_money,
+            )],
+            &[&rich_boi],
+        )
+        .assert_success();
+        println!("[*] rich boi payed his bills");
+
+        env.add_invariant(Invariant::can_execute(
+            "wallet authority can withdraw all funds",
+            &[level1::withdraw(
+                wallet_program,
+                wallet_authority.pubkey(),
+                wallet_authority.pubkey(),
+                a_lot_of_money,
+            )],
+            &[&wallet_authority],
+        ));
+        env.add_invariant(Invariant::program_lamports_never_decrease(
+            &env,
+            wallet_program,
+            &[wallet_authority.pubkey()],
+        ));
+
+        (
+            env,
+            Level1 {
+                wallet_address,
+                hacker,
+                wallet_program,
+                wallet_authority: wallet_authority.pubkey(),
+            },
+        )
+    }
+}
+
+pub fn main() {
+    pocs::run::<Level1>();
+}
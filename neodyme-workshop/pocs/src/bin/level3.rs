@@ -0,0 +1,146 @@
+use std::str::FromStr;
+
+use poc_framework::solana_sdk::signature::Keypair;
+use poc_framework::{
+    assert::Invariant, keypair, processor,
+    solana_program_runtime::invoke_context::ProcessInstructionWithContext,
+    solana_sdk::signer::Signer, Environment, LocalEnvironment, PrintableTransaction,
+};
+use pocs::{Challenge, Program};
+
+use solana_program::{
+    native_token::sol_to_lamports, pubkey::Pubkey, system_instruction, system_program,
+};
+
+#[allow(dead_code)]
+struct Level3 {
+    hacker: Keypair,
+    tip_program: Pubkey,
+    initializer: Pubkey,
+    vault_address: Pubkey,
+    pool_address: Pubkey,
+}
+
+impl Challenge for Level3 {
+    const PROGRAM: &'static str = "level3";
+    const PROCESSOR: ProcessInstructionWithContext =
+        processor!(level3::processor::process_instruction);
+
+    // Do your hacks in this function here
+    fn hack(&self, _env: &mut LocalEnvironment) {}
+
+    fn attackers(&self) -> Vec<Pubkey> {
+        vec![self.hacker.pubkey()]
+    }
+
+    /*
+    SETUP CODE BELOW
+    */
+    fn setup(program: &Program) -> (LocalEnvironment, Self) {
+        let tip_program = Pubkey::from_str("T1p1111111111111111111111111111111111111111").unwrap();
+        let initializer = keypair(0);
+        let poor_boi = keypair(1);
+        let rich_boi = keypair(2);
+        let pool = keypair(3);
+        let hacker = keypair(42);
+
+        let tip_amount = sol_to_lamports(42.0);
+
+        let mut builder = LocalEnvironment::builder();
+        program.add_to(&mut builder, tip_program);
+        let mut env = builder
+            .add_account_with_lamports(
+                initializer.pubkey(),
+                system_program::ID,
+                sol_to_lamports(100.0),
+            )
+            .add_account_with_lamports(poor_boi.pubkey(), system_program::ID, 0)
+            .add_account_with_lamports(
+                rich_boi.pubkey(),
+                system_program::ID,
+                sol_to_lamports(100.0),
+            )
+            .add_account_with_lamports(hacker.pubkey(), system_program::ID, sol_to_lamports(1.0))
+            .build();
+
+        let (vault_address, seed) = Pubkey::find_program_address(&[], &tip_program);
+
+        // Create Vault
+        env.execute_as_transaction(
+            &[level3::initialize(
+                tip_program,
+                vault_address,
+                initializer.pubkey(),
+                seed,
+                0.0,
+                initializer.pubkey(),
+            )],
+            &[&initializer],
+        )
+        .assert_success();
+        println!("[*] Vault created!");
+
+        // Create Pool
+        env.execute_as_transaction(
+            &[
+                system_instruction::create_account(
+                    &initializer.pubkey(),
+                    &pool.pubkey(),
+                    env.get_rent_excemption(level3::TIP_POOL_LEN as usize),
+                    level3::TIP_POOL_LEN,
+                    &tip_program,
+                ),
+                level3::create_pool(tip_program, vault_address, poor_boi.pubkey(), pool.pubkey()),
+            ],
+            &[&initializer, &pool, &poor_boi],
+        )
+        .assert_success();
+        println!("[*] Pool created!");
+
+        // rich boi tips the poor boi
+        env.execute_as_transaction(
+            &[level3::tip(
+                tip_program,
+                vault_address,
+                pool.pubkey(),
+                rich_boi.pubkey(),
+                tip_amount,
+            )],
+            &[&rich_boi],
+        )
+        .assert_success();
+        println!("[*] rich boi tipped the poor boi");
+
+        env.add_invariant(Invariant::can_execute(
+            "poor boi can withdraw his tips",
+            &[level3::withdraw(
+                tip_program,
+                vault_address,
+                pool.pubkey(),
+                poor_boi.pubkey(),
+                tip_amount,
+            )],
+            &[&poor_boi],
+        ));
+        env.add_invariant(Invariant::program_lamports_never_decrease(
+            &env,
+            tip_program,
+            &[poor_boi.pubkey()],
+        ));
+
+        (
+            env,
+            Level3 {
+                hacker,
+                tip_program,
+                initializer: initializer.pubkey(),
+                vault_address,
+                pool_address: pool.pubkey(),
+            },
+        )
+    }
+}
+
+pub fn main() {
+    pocs::run::<Level3>();
+}
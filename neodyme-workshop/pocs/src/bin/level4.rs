@@ -0,0 +1,134 @@
+use std::str::FromStr;
+
+use poc_framework::solana_sdk::signature::Keypair;
+use poc_framework::{
+    assert::Invariant, keypair, processor,
+    solana_program_runtime::invoke_context::ProcessInstructionWithContext,
+    solana_sdk::signer::Signer, Environment, LocalEnvironment, PrintableTransaction,
+};
+use pocs::{Challenge, Program};
+
+use solana_program::{native_token::sol_to_lamports, pubkey::Pubkey, system_program};
+
+#[allow(dead_code)]
+struct Level4 {
+    hacker: Keypair,
+    hacker_token_account: Pubkey,
+    wallet_program: Pubkey,
+    wallet_owner: Pubkey,
+    wallet_address: Pubkey,
+    mint: Pubkey,
+}
+
+impl Challenge for Level4 {
+    const PROGRAM: &'static str = "level4";
+    const PROCESSOR: ProcessInstructionWithContext =
+        processor!(level4::processor::process_instruction);
+
+    // Do your hacks in this function here
+    fn hack(&self, _env: &mut LocalEnvironment) {}
+
+    fn attackers(&self) -> Vec<Pubkey> {
+        vec![self.hacker.pubkey()]
+    }
+
+    /*
+    SETUP CODE BELOW
+    */
+    fn setup(program: &Program) -> (LocalEnvironment, Self) {
+        let wallet_program =
+            Pubkey::from_str("W4113t3333333333333333333333333333333333333").unwrap();
+        let wallet_owner = keypair(0);
+        let rich_boi = keypair(1);
+        let mint = keypair(2).pubkey();
+        let owner_token_account = keypair(3).pubkey();
+        let rich_boi_token_account = keypair(4).pubkey();
+        let hacker_token_account = keypair(5).pubkey();
+        let hacker = keypair(42);
+
+        let a_lot_of_money = 1_000_000_000_000;
+
+        let mut builder = LocalEnvironment::builder();
+        program.add_to(&mut builder, wallet_program);
+        let mut env = builder
+            .add_account_with_lamports(
+                wallet_owner.pubkey(),
+                system_program::ID,
+                sol_to_lamports(100.0),
+            )
+            .add_account_with_lamports(
+                rich_boi.pubkey(),
+                system_program::ID,
+                sol_to_lamports(100.0),
+            )
+            .add_account_with_lamports(hacker.pubkey(), system_program::ID, sol_to_lamports(2.0))
+            .add_token_mint(mint, None, a_lot_of_money, 9, None)
+            .add_account_with_tokens(owner_token_account, mint, wallet_owner.pubkey(), 0)
+            .add_account_with_tokens(
+                rich_boi_token_account,
+                mint,
+                rich_boi.pubkey(),
+                a_lot_of_money,
+            )
+            .add_account_with_tokens(hacker_token_account, mint, hacker.pubkey(), 0)
+            .build();
+
+        let (wallet_address, _) =
+            level4::get_wallet_address(&wallet_owner.pubkey(), &wallet_program);
+
+        // Create Wallet
+        env.execute_as_transaction(
+            &[level4::initialize(
+                wallet_program,
+                wallet_owner.pubkey(),
+                mint,
+            )],
+            &[&wallet_owner],
+        )
+        .assert_success();
+        println!("[*] Wallet created!");
+
+        // rich boi pays for bill
+        env.execute_as_transaction(
+            &[level4::deposit(
+                wallet_program,
+                wallet_owner.pubkey(),
+                rich_boi_token_account,
+                rich_boi.pubkey(),
+                mint,
+                a_lot_of_money,
+            )],
+            &[&rich_boi],
+        )
+        .assert_success();
+        println!("[*] rich boi payed his bills");
+
+        env.add_invariant(Invariant::can_execute(
+            "wallet owner can withdraw all funds",
+            &[level4::withdraw(
+                wallet_program,
+                wallet_owner.pubkey(),
+                owner_token_account,
+                mint,
+                a_lot_of_money,
+            )],
+            &[&wallet_owner],
+        ));
+
+        (
+            env,
+            Level4 {
+                hacker,
+                hacker_token_account,
+                wallet_program,
+                wallet_owner: wallet_owner.pubkey(),
+                wallet_address,
+                mint,
+            },
+        )
+    }
+}
+
+pub fn main() {
+    pocs::run::<Level4>();
+}
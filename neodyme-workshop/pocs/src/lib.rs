@@ -0,0 +1,117 @@
+use std::{env, path::PathBuf};
+
+use poc_framework::{
+    assert::BalanceTracker, assert::Report,
+    solana_program_runtime::invoke_context::ProcessInstructionWithContext, LocalEnvironment,
+    LocalEnvironmentBuilder,
+};
+use solana_program::pubkey::Pubkey;
+
+/// How the challenge program is loaded into the environment.
+pub enum Program {
+    /// The program built with `cargo build-bpf`, run in the BPF VM like on chain.
+    Bpf(PathBuf),
+    /// The `process_instruction` of the contract crate, run natively. Breakpoints and `println!` work in there.
+    Native(ProcessInstructionWithContext),
+}
+
+impl Program {
+    /// Adds the program at the address into the environment.
+    pub fn add_to(&self, builder: &mut LocalEnvironmentBuilder, pubkey: Pubkey) {
+        match self {
+            Program::Bpf(path) => builder.add_program(pubkey, path),
+            Program::Native(process_instruction) => {
+                builder.add_builtin_program("challenge", pubkey, *process_instruction)
+            }
+        };
+    }
+}
+
+/// A workshop level. Sets up a vulnerable program, runs the hack of the player against it and checks whether it worked.
+pub trait Challenge: Sized {
+    /// The name of the challenge program, which is built to `contracts/target/deploy/<PROGRAM>.so`.
+    const PROGRAM: &'static str;
+
+    /// The native processor of the challenge program, created with [poc_framework::processor].
+    const PROCESSOR: ProcessInstructionWithContext;
+
+    /// Builds the environment with the challenge program.
+    /// Returns everything the player may know. Secrets, like the keypairs of the victims, stay in here,
+    /// invariants that need them are added to the environment instead.
+    fn setup(program: &Program) -> (LocalEnvironment, Self);
+
+    /// The keys of the player, whose profit is measured.
+    fn attackers(&self) -> Vec<Pubkey>;
+
+    /// The exploit of the player.
+    fn hack(&self, env: &mut LocalEnvironment);
+
+    /// Judges the hack. Prints the report of the attacker holdings and invariants by default.
+    /// Returns whether the exploit succeeded.
+    fn verify(&self, _env: &mut LocalEnvironment, report: Report) -> bool {
+        report.print();
+        report.exploit_succeeded()
+    }
+}
+
+/// Sets up the challenge, runs the hack and verifies it. Returns whether the exploit succeeded.
+/// The built program is used if there is one, unless `CHALLENGE_NATIVE` is set. Otherwise the native processor runs.
+pub fn run<C: Challenge>() -> bool {
+    let program = match find_program(C::PROGRAM) {
+        Some(path) if env::var_os("CHALLENGE_NATIVE").is_none() => {
+            println!("[*] Loading {}", path.display());
+            Program::Bpf(path)
+        }
+        _ => {
+            println!("[*] Running the native processor of {}", C::PROGRAM);
+            Program::Native(C::PROCESSOR)
+        }
+    };
+    let (mut env, challenge) = C::setup(&program);
+    let tracker = BalanceTracker::new(&env, &challenge.attackers());
+
+    let snapshot = env.snapshot();
+    challenge.hack(&mut env);
+    println!("[*] Accounts changed by the hack:");
+    for diff in env.diff_since(&snapshot) {
+        print!("{}", diff);
+    }
+
+    let report = env.report(&tracker);
+    challenge.verify(&mut env, report)
+}
+
+/// Finds the built program with the given name.
+/// The directory in `CHALLENGE_PROGRAM_DIR` is searched first, then `contracts/target/deploy` in every parent directory
+/// of this crate and of the executable.
+pub fn find_program(name: &str) -> Option<PathBuf> {
+    let file = format!("{}.so", name);
+    let mut candidates = vec![];
+    if let Some(dir) = env::var_os("CHALLENGE_PROGRAM_DIR") {
+        candidates.push(PathBuf::from(dir).join(&file));
+    }
+    candidates.extend(search_dirs("contracts/target/deploy").map(|dir| dir.join(&file)));
+
+    candidates.into_iter().find(|candidate| candidate.is_file())
+}
+
+/// Finds the prebuilt program with the given name in `local-node` in every parent directory of this crate and of the executable.
+/// These may be older than the contracts, so the levels never use them.
+pub fn find_prebuilt_program(name: &str) -> Option<PathBuf> {
+    let file = format!("{}.so", name);
+    search_dirs("local-node")
+        .map(|dir| dir.join(&file))
+        .find(|candidate| candidate.is_file())
+}
+
+/// The directory at the relative path in every parent directory of this crate and of the executable.
+fn search_dirs(path: &'static str) -> impl Iterator<Item = PathBuf> {
+    let exe = env::current_exe().expect("couldn't locate the executable");
+    vec![PathBuf::from(env!("CARGO_MANIFEST_DIR")), exe]
+        .into_iter()
+        .flat_map(move |root| {
+            root.ancestors()
+                .map(|dir| dir.join(path))
+                .collect::<Vec<_>>()
+        })
+}